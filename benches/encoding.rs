@@ -0,0 +1,82 @@
+//! Compares the output formats of `semantic_tokens_encoded` and `autocomplete_encoded` on a
+//! file of about 5000 tokens: `cargo bench --bench encoding`.
+//!
+//! `objects` builds JS objects and needs a JS engine, here it is approximated by building a
+//! `serde_json::Value` tree, which walks the same fields one by one. Numbers from a browser
+//! will be higher for it, as every field crosses the wasm boundary.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use typst_flow_wasm::SuiteCore;
+
+const TOKENS: usize = 5000;
+const RUNS: u32 = 20;
+
+/// A document of repeated headings, markup and code with at least `TOKENS` tokens.
+fn source(core: &mut SuiteCore) -> String {
+    let block = "= Heading <label>\n\
+        Some *strong* and _emph_ text with `raw` and $x^2 + y$.\n\
+        #let add(a, b: 1) = a + b\n\
+        #for i in range(3) [#add(i, b: 2) @label]\n";
+    let mut text = String::new();
+    loop {
+        text.push_str(block);
+        core.set_file("main.typ".into(), text.clone()).unwrap();
+        if core.semantic_tokens("main.typ".into()).unwrap().len() >= TOKENS {
+            return text;
+        }
+    }
+}
+
+/// Mean time of `f` over `RUNS` runs.
+fn measure<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut result = f();
+    let started = Instant::now();
+    for _ in 0..RUNS {
+        result = f();
+    }
+    (started.elapsed() / RUNS, result)
+}
+
+fn compare<T: Serialize>(name: &str, value: &T) {
+    let (objects, _) = measure(|| serde_json::to_value(value).unwrap());
+    let (json, json_string) = measure(|| serde_json::to_string(value).unwrap());
+    let (msgpack, bytes) = measure(|| rmp_serde::to_vec_named(value).unwrap());
+
+    println!("{}", name);
+    println!("  objects        {:>10.2?}", objects);
+    println!(
+        "  json_string    {:>10.2?}  {:>8} bytes",
+        json,
+        json_string.len()
+    );
+    println!(
+        "  msgpack_bytes  {:>10.2?}  {:>8} bytes",
+        msgpack,
+        bytes.len()
+    );
+}
+
+fn main() {
+    let mut core = SuiteCore::new("main.typ".into());
+    core.add_file("main.typ".into(), String::new()).unwrap();
+    let mut text = source(&mut core);
+
+    let (elapsed, tokens) = measure(|| core.semantic_tokens("main.typ".into()).unwrap());
+    println!("{} tokens computed in {:.2?}", tokens.len(), elapsed);
+    compare("semantic_tokens", &tokens);
+
+    // After `#` everything in the global scope is offered
+    text.push('#');
+    core.set_file("main.typ".into(), text.clone()).unwrap();
+    let (elapsed, completions) =
+        measure(|| core.autocomplete("main.typ".into(), text.len(), None, None, None));
+    let completions = completions.unwrap();
+    println!(
+        "{} completions computed in {:.2?}",
+        completions.len(),
+        elapsed
+    );
+    compare("autocomplete", &completions);
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use typst::{
+    foundations::{Content, NativeElement, Repr, Selector, Value},
+    layout::Transform,
+    math::EquationElem,
+    model::Document,
+    syntax::Span,
+    World, WorldExt,
+};
+
+use crate::{
+    frames::{item_bounds, item_spans, walk_items, Bounds},
+    js_types::{EquationAltText, ResolvedSpan},
+};
+
+/// Spoken form of a few common math symbols, everything else is read as is.
+fn speak_symbol(text: &str) -> Option<&'static str> {
+    Some(match text {
+        "+" => "plus",
+        "-" | "−" => "minus",
+        "=" => "equals",
+        "≠" => "not equal to",
+        "<" => "less than",
+        ">" => "greater than",
+        "≤" => "less than or equal to",
+        "≥" => "greater than or equal to",
+        "±" => "plus or minus",
+        "·" | "⋅" | "×" => "times",
+        "÷" | "/" => "divided by",
+        "∞" => "infinity",
+        "∑" => "the sum",
+        "∏" => "the product",
+        "∫" => "the integral",
+        "∂" => "partial",
+        "→" => "to",
+        "∈" => "in",
+        "(" => "open parenthesis",
+        ")" => "close parenthesis",
+        "[" => "open bracket",
+        "]" => "close bracket",
+        "{" => "open brace",
+        "}" => "close brace",
+        "|" => "vertical bar",
+        "," => ",",
+        _ => return None,
+    })
+}
+
+pub struct Speech {
+    pub text: String,
+    pub unsupported: bool, // Whether some construct could not be spoken
+}
+
+/// Builds a speech string from the content tree of an equation.
+pub fn speak(content: &Content) -> Speech {
+    let mut speaker = Speaker { unsupported: false };
+    let text = speaker.content(content);
+
+    Speech {
+        text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+        unsupported: speaker.unsupported,
+    }
+}
+
+struct Speaker {
+    unsupported: bool,
+}
+
+impl Speaker {
+    fn field(&mut self, content: &Content, name: &str) -> Option<String> {
+        match content.get_by_name(name).ok()? {
+            Value::None => None,
+            value => Some(self.value(&value)),
+        }
+    }
+
+    fn value(&mut self, value: &Value) -> String {
+        match value {
+            Value::Content(content) => self.content(content),
+            Value::Array(array) => array
+                .iter()
+                .map(|v| self.value(v))
+                .collect::<Vec<String>>()
+                .join(", "),
+            Value::Str(s) => s.to_string(),
+            value => value.repr().to_string(),
+        }
+    }
+
+    fn script(&mut self, content: &Content, name: &str, prefix: &str) -> String {
+        match self.field(content, name) {
+            Some(script) => match (name, script.as_str()) {
+                ("t" | "tr", "2") => " squared".to_string(),
+                ("t" | "tr", "3") => " cubed".to_string(),
+                _ => format!(" {} {} end {}", prefix, script, prefix),
+            },
+            None => String::new(),
+        }
+    }
+
+    fn content(&mut self, content: &Content) -> String {
+        let name = content.elem().name();
+        match name {
+            "sequence" => match content.get_by_name("children") {
+                Ok(Value::Array(children)) => children
+                    .iter()
+                    .map(|child| format!(" {}", self.value(child)))
+                    .collect(),
+                _ => String::new(),
+            },
+            "styled" | "equation" | "lr" | "mid" | "class" | "scripts" | "limits" | "stretch" => {
+                ["child", "body"]
+                    .iter()
+                    .find_map(|field| self.field(content, field))
+                    .unwrap_or_default()
+            }
+            "text" | "op" => {
+                let text = self.field(content, "text").unwrap_or_default();
+                speak_symbol(text.trim()).map_or(text, str::to_string)
+            }
+            "space" | "h" | "align-point" => " ".to_string(),
+            "linebreak" => ", new line, ".to_string(),
+            "frac" => format!(
+                " fraction {} over {} end fraction ",
+                self.field(content, "num").unwrap_or_default(),
+                self.field(content, "denom").unwrap_or_default()
+            ),
+            "binom" => format!(
+                " {} choose {} ",
+                self.field(content, "upper").unwrap_or_default(),
+                self.field(content, "lower").unwrap_or_default()
+            ),
+            "attach" => {
+                let mut text = self.field(content, "base").unwrap_or_default();
+                text.push_str(&self.script(content, "b", "sub"));
+                text.push_str(&self.script(content, "br", "sub"));
+                text.push_str(&self.script(content, "t", "superscript"));
+                text.push_str(&self.script(content, "tr", "superscript"));
+                text.push_str(&self.script(content, "bl", "pre sub"));
+                text.push_str(&self.script(content, "tl", "pre superscript"));
+                text
+            }
+            "primes" => match content.get_by_name("count") {
+                Ok(Value::Int(1)) => " prime ".to_string(),
+                Ok(Value::Int(count)) => format!(" {} primes ", count),
+                _ => " prime ".to_string(),
+            },
+            "root" => {
+                let radicand = self.field(content, "radicand").unwrap_or_default();
+                match self.field(content, "index") {
+                    Some(index) => format!(" root {} of {} end root ", index, radicand),
+                    None => format!(" square root of {} end root ", radicand),
+                }
+            }
+            "accent" => format!(
+                " {} with accent {} ",
+                self.field(content, "base").unwrap_or_default(),
+                self.field(content, "accent").unwrap_or_default()
+            ),
+            "vec" => format!(
+                " vector {} end vector ",
+                self.field(content, "children").unwrap_or_default()
+            ),
+            "cases" => format!(
+                " cases {} end cases ",
+                self.field(content, "children").unwrap_or_default()
+            ),
+            "mat" => match content.get_by_name("rows") {
+                Ok(Value::Array(rows)) => {
+                    let rows = rows
+                        .iter()
+                        .enumerate()
+                        .map(|(i, row)| format!("row {}: {}", i + 1, self.value(row)))
+                        .collect::<Vec<String>>();
+                    format!(
+                        " matrix with {} rows, {} end matrix ",
+                        rows.len(),
+                        rows.join("; ")
+                    )
+                }
+                _ => " matrix ".to_string(),
+            },
+            "underline" | "overline" | "cancel" | "underbrace" | "overbrace" | "underbracket"
+            | "overbracket" => {
+                let body = self.field(content, "body").unwrap_or_default();
+                let annotation = self
+                    .field(content, "annotation")
+                    .map(|a| format!(" labeled {}", a))
+                    .unwrap_or_default();
+                format!(" {} {}{} ", name, body, annotation)
+            }
+            _ => {
+                self.unsupported = true;
+                format!(" [unsupported: {}] ", name)
+            }
+        }
+    }
+}
+
+/// Source text of an equation without the surrounding dollars and with collapsed whitespace.
+pub fn clean_math_source(text: &str) -> String {
+    text.trim()
+        .trim_start_matches('$')
+        .trim_end_matches('$')
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Accessible text and placement of every equation in a compiled document.
+pub fn equation_alt_texts(world: &impl World, doc: &Document) -> Vec<EquationAltText> {
+    let mut ranges = HashMap::new();
+    let mut range = |span: Span| {
+        ranges
+            .entry(span)
+            .or_insert_with(|| world.range(span))
+            .clone()
+    };
+
+    doc.introspector
+        .query(&Selector::Elem(EquationElem::elem(), None))
+        .iter()
+        .map(|equation| {
+            let span = equation.span();
+            let source_range = range(span);
+            let source = span.id().and_then(|id| world.source(id).ok());
+            let source_text = source
+                .as_ref()
+                .zip(source_range.clone())
+                .and_then(|(source, r)| source.text().get(r).map(clean_math_source))
+                .unwrap_or_else(|| equation.plain_text().to_string());
+
+            let page = equation
+                .location()
+                .map(|loc| doc.introspector.position(loc).page.get() - 1)
+                .unwrap_or(0);
+
+            // Everything laid out from spans inside the equation's source belongs to it
+            let mut bounds: Option<Bounds> = None;
+            if let (Some(frame), Some(outer)) =
+                (doc.pages.get(page).map(|p| &p.frame), &source_range)
+            {
+                walk_items(frame, Transform::identity(), &mut |item, ts| {
+                    let inside = item_spans(item).into_iter().any(|s| {
+                        s.id() == span.id()
+                            && range(s)
+                                .is_some_and(|r| outer.start <= r.start && r.end <= outer.end)
+                    });
+                    if inside {
+                        if let Some(b) = item_bounds(item, ts) {
+                            bounds = Some(bounds.map_or(b, |bounds| bounds.union(b)));
+                        }
+                    }
+                });
+            }
+
+            let speech = speak(equation);
+            EquationAltText {
+                page,
+                x_pt: bounds.map_or(0.0, |b| b.min.x.to_pt()),
+                y_pt: bounds.map_or(0.0, |b| b.min.y.to_pt()),
+                width_pt: bounds.map_or(0.0, |b| b.width().to_pt()),
+                height_pt: bounds.map_or(0.0, |b| b.height().to_pt()),
+                block: matches!(equation.get_by_name("block"), Ok(Value::Bool(true))),
+                source_text,
+                speech: speech.text,
+                unsupported: speech.unsupported,
+                span: source.map_or_else(
+                    || ResolvedSpan::detached(span),
+                    |source| ResolvedSpan::from_source(span, &source),
+                ),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use typst::{
+    layout::{Abs, FrameItem, Page, Transform},
+    model::Document,
+    syntax::{FileId, Span},
+};
+
+use crate::{
+    file_entry::FileEntry,
+    frames::{item_spans, walk_items},
+};
+
+/// The class attributes typst-svg writes on text groups and shape paths.
+const MARKERS: [&str; 2] = ["class=\"typst-text\"", "class=\"typst-shape\""];
+
+/// One entry per text group and shape path typst-svg writes for a page, in the same order.
+fn page_spans(page: &Page, spans: &mut Vec<Option<Span>>) {
+    // The page background is rendered as a shape without a span
+    if page.fill_or_white().is_some() {
+        spans.push(None);
+    }
+    walk_items(&page.frame, Transform::identity(), &mut |item, _| {
+        if matches!(item, FrameItem::Text(_) | FrameItem::Shape(..)) {
+            spans.push(item_spans(item).into_iter().find(|s| !s.is_detached()));
+        }
+    });
+}
+
+/// `path:start:end` of a span in the project or a loaded package, byte offsets.
+fn span_attribute(span: Span, sources: &HashMap<FileId, FileEntry>) -> Option<String> {
+    let id = span.id()?;
+    let range = sources.get(&id)?.source.range(span)?;
+    let path = crate::paths::qualified_path(id)
+        .to_string_lossy()
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;");
+    Some(format!("{}:{}:{}", path, range.start, range.end))
+}
+
+/// Adds a `data-span` attribute to every text group and shape path of `svg` that stems from
+/// a loaded source.
+fn annotate(svg: &str, spans: Vec<Option<Span>>, sources: &HashMap<FileId, FileEntry>) -> String {
+    let mut out = String::with_capacity(svg.len() * 2);
+    let mut rest = svg;
+    for span in spans {
+        let Some((start, marker)) = MARKERS
+            .iter()
+            .filter_map(|marker| Some((rest.find(marker)?, marker)))
+            .min_by_key(|(start, _)| *start)
+        else {
+            break;
+        };
+        let end = start + marker.len();
+        out.push_str(&rest[..end]);
+        if let Some(attribute) = span.and_then(|span| span_attribute(span, sources)) {
+            out.push_str(" data-span=\"");
+            out.push_str(&attribute);
+            out.push('"');
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `typst_svg::svg` with source spans on the rendered items.
+pub fn svg(page: &Page, sources: &HashMap<FileId, FileEntry>) -> String {
+    let mut spans = Vec::new();
+    page_spans(page, &mut spans);
+    annotate(&typst_svg::svg(page), spans, sources)
+}
+
+/// `typst_svg::svg_merged` with source spans on the rendered items.
+pub fn svg_merged(doc: &Document, gap: Abs, sources: &HashMap<FileId, FileEntry>) -> String {
+    let mut spans = Vec::new();
+    for page in &doc.pages {
+        page_spans(page, &mut spans);
+    }
+    annotate(&typst_svg::svg_merged(doc, gap), spans, sources)
+}
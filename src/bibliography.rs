@@ -0,0 +1,166 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use typst::syntax::{ast, FileId, LinkedNode, Source, SyntaxKind};
+
+use crate::{
+    file_entry::FileEntry,
+    js_types::{Completion, CompletionDetail, CompletionKind, Diagnostics, ResolvedSpan, Severity},
+};
+
+/// The paths passed to `bibliography`, a single string or an array of them.
+fn collect_paths(node: &LinkedNode, source: &Source, ids: &mut Vec<FileId>) {
+    if let Some(call) = node.cast::<ast::FuncCall>() {
+        let is_bibliography =
+            matches!(call.callee(), ast::Expr::Ident(ident) if ident.as_str() == "bibliography");
+        let first = call.args().items().find_map(|arg| match arg {
+            ast::Arg::Pos(expr) => Some(expr),
+            _ => None,
+        });
+        let paths = match first.filter(|_| is_bibliography) {
+            Some(ast::Expr::Str(path)) => vec![path.get()],
+            Some(ast::Expr::Array(array)) => array
+                .items()
+                .filter_map(|item| match item {
+                    ast::ArrayItem::Pos(ast::Expr::Str(path)) => Some(path.get()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        for path in paths {
+            let id = FileId::new(None, source.id().vpath().join(path.as_str()));
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    for child in node.children() {
+        collect_paths(&child, source, ids);
+    }
+}
+
+/// The bibliography files loaded by the project sources, in the order they are found.
+pub fn bibliography_files(sources: &HashMap<FileId, FileEntry>) -> Vec<FileId> {
+    let mut project = sources
+        .iter()
+        .filter(|(id, _)| id.package().is_none())
+        .collect::<Vec<_>>();
+    project.sort_by_key(|(id, _)| id.vpath().as_rooted_path().to_path_buf());
+
+    let mut ids = Vec::new();
+    for (_, entry) in project {
+        collect_paths(
+            &LinkedNode::new(entry.source.root()),
+            &entry.source,
+            &mut ids,
+        );
+    }
+    ids
+}
+
+/// The entry keys of a BibLaTeX (`.bib`) or Hayagriva (`.yml`, `.yaml`) file.
+pub fn parse_keys(path: &str, data: &[u8]) -> Result<Vec<String>, String> {
+    let text = std::str::from_utf8(data).map_err(|_| "file is not valid UTF-8".to_string())?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let library = match extension.as_str() {
+        "yml" | "yaml" => hayagriva::io::from_yaml_str(text)
+            .map_err(|e| format!("failed to parse YAML ({})", e))?,
+        "bib" => hayagriva::io::from_biblatex_str(text).map_err(|errors| {
+            errors
+                .iter()
+                .map(|e| format!("failed to parse BibLaTeX ({})", e))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })?,
+        _ => return Err("unknown bibliography format (must be .yml/.yaml or .bib)".to_string()),
+    };
+
+    Ok(library
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect())
+}
+
+fn collect_citations(
+    node: &LinkedNode,
+    source: &Source,
+    keys: &HashSet<String>,
+    labels: &HashSet<String>,
+    diagnostics: &mut Vec<Diagnostics>,
+) {
+    let cited = match node.kind() {
+        SyntaxKind::Ref => node
+            .cast::<ast::Ref>()
+            .map(|reference| reference.target().to_string()),
+        // `cite(<key>)`, the label is its first argument
+        SyntaxKind::Label if node.parent_kind() == Some(SyntaxKind::Args) => node
+            .parent()
+            .and_then(|args| args.parent())
+            .and_then(|call| call.cast::<ast::FuncCall>())
+            .filter(
+                |call| matches!(call.callee(), ast::Expr::Ident(ident) if ident.as_str() == "cite"),
+            )
+            .and_then(|_| node.cast::<ast::Label>())
+            .map(|label| label.get().to_string()),
+        _ => None,
+    };
+
+    if let Some(key) = cited.filter(|key| !keys.contains(key) && !labels.contains(key)) {
+        diagnostics.push(Diagnostics {
+            severity: Severity::Warning,
+            message: format!("key `{}` does not exist in the bibliography", key),
+            root: ResolvedSpan::from_source(node.span(), source),
+            hints: vec!["check the spelling or add the entry to a bibliography file".to_string()],
+            trace: Vec::new(),
+            trace_points: Vec::new(),
+            promoted: false,
+            fixes: Vec::new(),
+        });
+    }
+
+    for child in node.children() {
+        collect_citations(&child, source, keys, labels, diagnostics);
+    }
+}
+
+/// Warnings for `@key` references and `cite(<key>)` calls in `source` whose key is neither
+/// a bibliography entry nor a label of the document.
+pub fn unknown_citations(
+    source: &Source,
+    keys: &HashSet<String>,
+    labels: &HashSet<String>,
+) -> Vec<Diagnostics> {
+    let mut diagnostics = Vec::new();
+    collect_citations(
+        &LinkedNode::new(source.root()),
+        source,
+        keys,
+        labels,
+        &mut diagnostics,
+    );
+    diagnostics
+}
+
+/// Completions for bibliography keys, shown next to the labels after `@`.
+pub fn key_completions(keys: Vec<String>) -> Vec<Completion> {
+    keys.into_iter()
+        .map(|key| Completion {
+            kind: CompletionDetail {
+                kind: CompletionKind::Constant,
+                detail: None,
+            },
+            label: key,
+            apply: None,
+            detail: Some("bibliography entry".to_string()),
+        })
+        .collect()
+}
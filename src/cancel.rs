@@ -0,0 +1,54 @@
+use js_sys::{Atomics, Int32Array, SharedArrayBuffer};
+use wasm_bindgen::prelude::*;
+
+/// Flag the host sets to cancel compiles, backed by a `SharedArrayBuffer` so another thread
+/// can set it while a compile blocks the worker running it. Falls back to a plain
+/// `ArrayBuffer` when the page is not cross-origin isolated.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct CancellationToken {
+    flag: Int32Array,
+}
+
+// SAFETY: The `Int32Array` handle is a JS object owned by the thread (worker) that created
+// the token and is only ever used there, wasm objects are not shared between JS threads.
+// Other threads reach the flag through `buffer`, posted to them as a `SharedArrayBuffer`,
+// and only write it with `Atomics`, which is what `cancel`, `reset` and `is_cancelled` use
+// here as well. This is only needed because `World` requires `Send + Sync`.
+unsafe impl Send for CancellationToken {}
+unsafe impl Sync for CancellationToken {}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        let shared =
+            js_sys::Reflect::has(&js_sys::global(), &"SharedArrayBuffer".into()).unwrap_or(false);
+        let flag = if shared {
+            Int32Array::new(&SharedArrayBuffer::new(4))
+        } else {
+            Int32Array::new_with_length(1)
+        };
+        Self { flag }
+    }
+}
+
+#[wasm_bindgen]
+impl CancellationToken {
+    /// Cancels the running compile and every following one until `reset` is called.
+    pub fn cancel(&self) {
+        let _ = Atomics::store(&self.flag, 0, 1);
+    }
+
+    pub fn reset(&self) {
+        let _ = Atomics::store(&self.flag, 0, 0);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        Atomics::load(&self.flag, 0).is_ok_and(|value| value != 0)
+    }
+
+    /// The underlying buffer, for posting to the thread that cancels. Setting its first
+    /// `Int32` to a non-zero value with `Atomics.store` equals calling `cancel`.
+    pub fn buffer(&self) -> JsValue {
+        self.flag.buffer().into()
+    }
+}
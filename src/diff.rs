@@ -0,0 +1,160 @@
+use std::ops::Range;
+
+// Above this many line pairs the LCS table gets too large, fall back to a single edit
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Debug)]
+pub struct Edit {
+    pub range: Range<usize>, // Byte range in the old text
+    pub text: String,
+}
+
+/// Computes a minimal list of non-overlapping edits (ascending, relative to `old`)
+/// that turn `old` into `new`. Lines are diffed first, changed hunks are then
+/// trimmed to the part that actually differs.
+pub fn diff(old: &str, new: &str) -> Vec<Edit> {
+    let (prefix, suffix) = line_affix(old, new);
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return Vec::new();
+    }
+
+    let old_lines = lines(old_mid);
+    let new_lines = lines(new_mid);
+
+    if old_lines.len().saturating_mul(new_lines.len()) > MAX_LCS_CELLS {
+        return vec![Edit {
+            range: prefix..prefix + old_mid.len(),
+            text: new_mid.to_string(),
+        }];
+    }
+
+    line_hunks(&old_lines, &new_lines)
+        .into_iter()
+        .filter_map(|(old_range, new_range)| {
+            let old_start = line_offset(&old_lines, old_range.start, old_mid.len());
+            let old_end = line_offset(&old_lines, old_range.end, old_mid.len());
+            let new_start = line_offset(&new_lines, new_range.start, new_mid.len());
+            let new_end = line_offset(&new_lines, new_range.end, new_mid.len());
+
+            refine(
+                &old_mid[old_start..old_end],
+                &new_mid[new_start..new_end],
+                prefix + old_start,
+            )
+        })
+        .collect()
+}
+
+/// Byte lengths of the common prefix and suffix, on char boundaries and never overlapping.
+fn common_affix(a: &str, b: &str) -> (usize, usize) {
+    let prefix = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, ca), cb)| ca != cb)
+        .map(|((i, _), _)| i)
+        .unwrap_or(a.len().min(b.len()));
+
+    let max_suffix = a.len().min(b.len()) - prefix;
+    let mut suffix = 0;
+    for (ca, cb) in a[prefix..].chars().rev().zip(b[prefix..].chars().rev()) {
+        if ca != cb || suffix + ca.len_utf8() > max_suffix {
+            break;
+        }
+        suffix += ca.len_utf8();
+    }
+
+    (prefix, suffix)
+}
+
+/// Like `common_affix` but snapped to whole lines, so the line diff stays aligned.
+fn line_affix(a: &str, b: &str) -> (usize, usize) {
+    let (prefix, suffix) = common_affix(a, b);
+    let prefix = a[..prefix].rfind('\n').map_or(0, |i| i + 1);
+
+    let suffix_start = a.len() - suffix;
+    let suffix = if suffix_start == 0 || a.as_bytes()[suffix_start - 1] == b'\n' {
+        suffix
+    } else {
+        a[suffix_start..].find('\n').map_or(0, |i| suffix - (i + 1))
+    };
+
+    (prefix, suffix)
+}
+
+/// Lines including their terminator, paired with their byte offset.
+fn lines(text: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line)
+        })
+        .collect()
+}
+
+fn line_offset(lines: &[(usize, &str)], index: usize, len: usize) -> usize {
+    lines.get(index).map_or(len, |(offset, _)| *offset)
+}
+
+/// Ranges of differing lines (old lines, new lines) based on the longest common subsequence.
+fn line_hunks(old: &[(usize, &str)], new: &[(usize, &str)]) -> Vec<(Range<usize>, Range<usize>)> {
+    let (n, m) = (old.len(), new.len());
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[at(i, j)] = if old[i].1 == new[j].1 {
+                lcs[at(i + 1, j + 1)] + 1
+            } else {
+                lcs[at(i + 1, j)].max(lcs[at(i, j + 1)])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let mut hunk_start: Option<(usize, usize)> = None;
+    let (mut i, mut j) = (0, 0);
+
+    while i < n || j < m {
+        if i < n && j < m && old[i].1 == new[j].1 {
+            if let Some((si, sj)) = hunk_start.take() {
+                hunks.push((si..i, sj..j));
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        hunk_start.get_or_insert((i, j));
+        if j >= m || (i < n && lcs[at(i + 1, j)] >= lcs[at(i, j + 1)]) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    if let Some((si, sj)) = hunk_start {
+        hunks.push((si..n, sj..m));
+    }
+
+    hunks
+}
+
+/// Shrinks a changed hunk to the part that differs within its lines.
+fn refine(old: &str, new: &str, offset: usize) -> Option<Edit> {
+    let (prefix, suffix) = common_affix(old, new);
+    if prefix + suffix == old.len() && prefix + suffix == new.len() {
+        return None;
+    }
+
+    Some(Edit {
+        range: offset + prefix..offset + old.len() - suffix,
+        text: new[prefix..new.len() - suffix].to_string(),
+    })
+}
@@ -0,0 +1,21 @@
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+use crate::js_types::OutputFormat;
+
+/// Serializes a result in the requested format. Objects are the most convenient,
+/// a JSON string or MessagePack bytes avoid building JS objects field by field.
+pub fn encode<T: Serialize + ?Sized>(value: &T, format: OutputFormat) -> Result<JsValue, JsValue> {
+    match format {
+        OutputFormat::Objects => {
+            serde_wasm_bindgen::to_value(value).map_err(|e| JsValue::from_str(&e.to_string()))
+        }
+        OutputFormat::JsonString => serde_json::to_string(value)
+            .map(|json| JsValue::from_str(&json))
+            .map_err(|e| JsValue::from_str(&e.to_string())),
+        OutputFormat::MsgpackBytes => rmp_serde::to_vec_named(value)
+            .map(|bytes| js_sys::Uint8Array::from(bytes.as_slice()).into())
+            .map_err(|e| JsValue::from_str(&e.to_string())),
+        _ => Err(JsValue::from_str("unknown output format")),
+    }
+}
@@ -0,0 +1,68 @@
+use typst::syntax::{
+    ast::{self, AstNode},
+    LinkedNode, Source, SyntaxKind,
+};
+
+use crate::{
+    ast::is_function,
+    js_types::{ExportedSymbol, ResolvedSpan, TidyType},
+    tidy::{collect_tidy_doc, parse_doc_str},
+};
+
+/// Syntax whose bindings are scoped to it instead of the module.
+const SCOPING_KINDS: &[SyntaxKind] = &[
+    SyntaxKind::CodeBlock,
+    SyntaxKind::ContentBlock,
+    SyntaxKind::Closure,
+    SyntaxKind::ForLoop,
+    SyntaxKind::WhileLoop,
+];
+
+fn is_module_scope(node: &LinkedNode) -> bool {
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        if SCOPING_KINDS.contains(&node.kind()) {
+            return false;
+        }
+        parent = node.parent();
+    }
+    true
+}
+
+fn collect(node: &LinkedNode, source: &Source, symbols: &mut Vec<ExportedSymbol>) {
+    if let Some(binding) = node.cast::<ast::LetBinding>() {
+        let module_scope = is_module_scope(node);
+        let kind = if is_function(node) {
+            TidyType::Function
+        } else {
+            TidyType::Variable
+        };
+        for ident in binding.kind().bindings() {
+            let Some(ident) = node.find(ident.span()) else {
+                continue;
+            };
+            // Doc comments are collected from the ident's parent, as for definitions
+            let docs = ident.parent().map(|parent| {
+                parse_doc_str(ident.text().to_string(), collect_tidy_doc(parent.clone()))
+            });
+            symbols.push(ExportedSymbol {
+                name: ident.text().to_string(),
+                kind: kind.clone(),
+                docs,
+                module_scope,
+                span: ResolvedSpan::from_source(ident.span(), source),
+            });
+        }
+    }
+
+    for child in node.children() {
+        collect(&child, source, symbols);
+    }
+}
+
+/// Every `let` binding of a source in document order, nested ones included but marked.
+pub fn module_exports(source: &Source) -> Vec<ExportedSymbol> {
+    let mut symbols = Vec::new();
+    collect(&LinkedNode::new(source.root()), source, &mut symbols);
+    symbols
+}
@@ -2,7 +2,22 @@ use futures::executor::block_on;
 // use reqwest::blocking::Response;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::{spawn_local, JsFuture};
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use web_sys::{Request, RequestInit, RequestMode, Response, Window, WorkerGlobalScope};
+
+/// Starts `request` with the `fetch` of the current global scope, which is a `Window` on the
+/// main thread and a `WorkerGlobalScope` in web workers, where `web_sys::window()` is `None`.
+fn fetch_with_request(request: &Request) -> Result<js_sys::Promise, JsValue> {
+    let global = js_sys::global();
+    if let Some(window) = global.dyn_ref::<Window>() {
+        Ok(window.fetch_with_request(request))
+    } else if let Some(worker) = global.dyn_ref::<WorkerGlobalScope>() {
+        Ok(worker.fetch_with_request(request))
+    } else {
+        Err(JsValue::from_str(
+            "Unsupported: fetch is only available in a window or a worker",
+        ))
+    }
+}
 
 pub async fn fetch_url_internal(url: String) -> Result<JsValue, JsValue> {
     let opts = RequestInit::new();
@@ -10,13 +25,29 @@ pub async fn fetch_url_internal(url: String) -> Result<JsValue, JsValue> {
     opts.set_mode(RequestMode::Cors);
 
     let request = Request::new_with_str_and_init(&url, &opts)?;
-    let window = web_sys::window().unwrap();
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+    let resp_value = JsFuture::from(fetch_with_request(&request)?).await?;
     let resp: Response = resp_value.dyn_into()?;
 
     JsFuture::from(resp.text()?).await
 }
 
+/// Downloads `url` as bytes, failing with "HTTP <status>" on unsuccessful responses.
+pub async fn fetch_bytes(url: String) -> Result<Vec<u8>, JsValue> {
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let request = Request::new_with_str_and_init(&url, &opts)?;
+    let resp_value = JsFuture::from(fetch_with_request(&request)?).await?;
+    let resp: Response = resp_value.dyn_into()?;
+    if !resp.ok() {
+        return Err(JsValue::from_str(&format!("HTTP {}", resp.status())));
+    }
+
+    let buffer = JsFuture::from(resp.array_buffer()?).await?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
 pub fn xml_http_request(url: String) -> Result<web_sys::XmlHttpRequest, JsValue> {
     let xhr = web_sys::XmlHttpRequest::new()?;
     xhr.open("GET", &url)?;
@@ -7,27 +7,85 @@ use typst::{
     syntax::{FileId, Source},
 };
 
+const BOM: &str = "\u{feff}";
+
+/// `text` without a leading byte order mark and whether it had one.
+fn strip_bom(text: &str) -> (&str, bool) {
+    match text.strip_prefix(BOM) {
+        Some(rest) => (rest, true),
+        None => (text, false),
+    }
+}
+
 #[derive(Clone)]
 pub struct FileEntry {
     bytes: OnceLock<Bytes>,
-    pub source: Source,
+    pub source: Source, // Edit through `edit` and `replace`, so `bytes` stays in sync
+    utf8: bool,         // Binary files keep their bytes and an empty source
+    bom: bool,          // The source is parsed without the byte order mark, `bytes` keep it
 }
 
 impl FileEntry {
     pub fn new(id: FileId, text: String) -> Self {
+        let (stripped, bom) = strip_bom(&text);
+        let text = if bom { stripped.to_string() } else { text };
         Self {
             bytes: OnceLock::new(),
             source: Source::new(id, text),
+            utf8: true,
+            bom,
         }
     }
 
+    pub fn from_bytes(id: FileId, bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::new(id, text),
+            Err(err) => Self {
+                bytes: OnceLock::from(Bytes::from(err.into_bytes())),
+                source: Source::new(id, String::new()),
+                utf8: false,
+                bom: false,
+            },
+        }
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        self.utf8
+    }
+
+    /// Whether the text had a byte order mark, which is not part of `source` or its offsets.
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
     pub fn source(&self) -> Source {
         self.source.clone()
     }
 
+    /// Replaces the text, reparsing only what changed. Returns the reparsed range.
+    pub fn replace(&mut self, text: &str) -> std::ops::Range<usize> {
+        let (text, bom) = strip_bom(text);
+        self.bytes = OnceLock::new();
+        self.bom = bom;
+        self.source.replace(text)
+    }
+
+    /// Replaces `range` of the text (without the byte order mark) and returns the reparsed range.
+    pub fn edit(&mut self, range: std::ops::Range<usize>, text: &str) -> std::ops::Range<usize> {
+        self.bytes = OnceLock::new();
+        self.source.edit(range, text)
+    }
+
+    /// The exact content as data loading functions like `csv` and `read` see it.
     pub fn bytes(&self) -> Bytes {
         self.bytes
-            .get_or_init(|| Bytes::from(self.source.text().as_bytes()))
+            .get_or_init(|| {
+                if self.bom {
+                    Bytes::from([BOM.as_bytes(), self.source.text().as_bytes()].concat())
+                } else {
+                    Bytes::from(self.source.text().as_bytes())
+                }
+            })
             .clone()
     }
 }
@@ -0,0 +1,97 @@
+use typst::syntax::{LinkedNode, Source, SyntaxKind};
+
+use crate::js_types::{FoldingKind, FoldingRange};
+
+struct Folder<'a> {
+    source: &'a Source,
+    ranges: Vec<FoldingRange>,
+}
+
+impl Folder<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.source.byte_to_line(offset).unwrap_or(0)
+    }
+
+    /// Line of the last non-whitespace character before `end`.
+    fn last_content_line(&self, start: usize, end: usize) -> usize {
+        let content = self.source.text()[start..end].trim_end();
+        self.line(start + content.len().saturating_sub(1))
+    }
+
+    fn push(&mut self, start_line: usize, end_line: usize, kind: FoldingKind) {
+        if end_line > start_line {
+            self.ranges.push(FoldingRange {
+                start_line,
+                end_line,
+                kind,
+            });
+        }
+    }
+
+    fn walk(&mut self, node: &LinkedNode) {
+        let kind = match node.kind() {
+            SyntaxKind::CodeBlock => Some(FoldingKind::Code),
+            SyntaxKind::ContentBlock => Some(FoldingKind::Content),
+            SyntaxKind::Args => Some(FoldingKind::Args),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            self.push(self.line(node.offset()), self.line(node.range().end), kind);
+        }
+
+        if node.kind() == SyntaxKind::Markup {
+            self.sections(node);
+        }
+
+        for child in node.children() {
+            self.walk(&child);
+        }
+    }
+
+    /// Folds every heading up to the next heading of equal or higher level within the same markup.
+    fn sections(&mut self, markup: &LinkedNode) {
+        let headings = markup
+            .children()
+            .filter(|child| child.kind() == SyntaxKind::Heading)
+            .map(|heading| {
+                let depth = heading
+                    .children()
+                    .find(|n| n.kind() == SyntaxKind::HeadingMarker)
+                    .map_or(1, |marker| marker.text().len());
+                (heading.offset(), depth)
+            })
+            .collect::<Vec<(usize, usize)>>();
+
+        for (i, (offset, depth)) in headings.iter().enumerate() {
+            let end = headings[i + 1..]
+                .iter()
+                .find(|(_, next_depth)| next_depth <= depth)
+                .map_or(markup.range().end, |(next_offset, _)| *next_offset);
+
+            self.push(
+                self.line(*offset),
+                self.last_content_line(*offset, end),
+                FoldingKind::Heading,
+            );
+        }
+    }
+}
+
+/// Folding ranges of code blocks, content blocks, argument lists and heading sections.
+/// Ranges starting on the same line are collapsed into the outermost one.
+pub fn folding_ranges(source: &Source) -> Vec<FoldingRange> {
+    let mut folder = Folder {
+        source,
+        ranges: Vec::new(),
+    };
+    folder.walk(&LinkedNode::new(source.root()));
+
+    let mut ranges = folder.ranges;
+    ranges.sort_by(|a, b| {
+        a.start_line
+            .cmp(&b.start_line)
+            .then(b.end_line.cmp(&a.end_line))
+    });
+    ranges.dedup_by_key(|range| range.start_line);
+    ranges
+}
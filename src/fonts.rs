@@ -0,0 +1,121 @@
+use typst::{
+    diag::SourceDiagnostic,
+    layout::{FrameItem, Transform},
+    model::Document,
+    syntax::{ast, LinkedNode, Source},
+    text::{Font, FontBook, FontStyle},
+};
+
+use crate::{frames::walk_items, js_types::UsedFont};
+
+/// Prefix of the warning typst reports for a `font` family missing from the book.
+const UNKNOWN_FAMILY: &str = "unknown font family: ";
+
+/// Every font laid out in the document with its number of glyphs, in order of first use.
+/// `fonts` are the fonts of the world, their positions become the reported indices.
+pub fn used_fonts(doc: &Document, fonts: &[Font]) -> Vec<UsedFont> {
+    // Font, glyphs and notdef glyphs
+    let mut counts: Vec<(Font, usize, usize)> = Vec::new();
+    for page in &doc.pages {
+        walk_items(&page.frame, Transform::identity(), &mut |item, _| {
+            let FrameItem::Text(text) = item else {
+                return;
+            };
+            // Glyph 0 is the notdef box, drawn when no font covers a character
+            let notdef = text.glyphs.iter().filter(|glyph| glyph.id == 0).count();
+            match counts.iter_mut().find(|(font, _, _)| *font == text.font) {
+                Some((_, glyphs, notdefs)) => {
+                    *glyphs += text.glyphs.len();
+                    *notdefs += notdef;
+                }
+                None => counts.push((text.font.clone(), text.glyphs.len(), notdef)),
+            }
+        });
+    }
+
+    counts
+        .into_iter()
+        .map(|(font, glyphs, notdef_glyphs)| {
+            let info = font.info();
+            UsedFont {
+                index: fonts.iter().position(|other| *other == font),
+                family: info.family.clone(),
+                style: match info.variant.style {
+                    FontStyle::Normal => "normal",
+                    FontStyle::Italic => "italic",
+                    FontStyle::Oblique => "oblique",
+                }
+                .to_string(),
+                weight: info.variant.weight.to_number(),
+                stretch: info.variant.stretch.to_ratio().get(),
+                glyphs,
+                notdef_glyphs,
+            }
+        })
+        .collect()
+}
+
+/// The families named by "unknown font family" warnings.
+pub fn missing_from_warnings(warnings: &[SourceDiagnostic]) -> Vec<String> {
+    warnings
+        .iter()
+        .filter_map(|warning| warning.message.strip_prefix(UNKNOWN_FAMILY))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The `font` argument of `text`, a family or an array of families.
+fn font_families(args: ast::Args) -> Vec<String> {
+    let Some(value) = args.items().find_map(|arg| match arg {
+        ast::Arg::Named(named) if named.name().as_str() == "font" => Some(named.expr()),
+        _ => None,
+    }) else {
+        return Vec::new();
+    };
+
+    match value {
+        ast::Expr::Str(family) => vec![family.get().to_string()],
+        ast::Expr::Array(array) => array
+            .items()
+            .filter_map(|item| match item {
+                ast::ArrayItem::Pos(ast::Expr::Str(family)) => Some(family.get().to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn collect_families(node: &LinkedNode, families: &mut Vec<String>) {
+    let is_text =
+        |expr: ast::Expr| matches!(expr, ast::Expr::Ident(ident) if ident.as_str() == "text");
+    if let Some(rule) = node
+        .cast::<ast::SetRule>()
+        .filter(|rule| is_text(rule.target()))
+    {
+        families.extend(font_families(rule.args()));
+    } else if let Some(call) = node
+        .cast::<ast::FuncCall>()
+        .filter(|call| is_text(call.callee()))
+    {
+        families.extend(font_families(call.args()));
+    }
+
+    for child in node.children() {
+        collect_families(&child, families);
+    }
+}
+
+/// Families written in `set text(font: ..)` rules and `text(font: ..)` calls that the
+/// font book doesn't have. Computed families are not seen.
+pub fn missing_from_sources<'a>(
+    sources: impl IntoIterator<Item = &'a Source>,
+    book: &FontBook,
+) -> Vec<String> {
+    let mut families = Vec::new();
+    for source in sources {
+        collect_families(&LinkedNode::new(source.root()), &mut families);
+    }
+    families.retain(|family| !book.contains_family(&family.to_lowercase()));
+    families
+}
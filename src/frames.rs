@@ -0,0 +1,146 @@
+use typst::{
+    layout::{Abs, Frame, FrameItem, Point, Transform},
+    syntax::Span,
+};
+
+/// Calls `f` for every leaf item of a frame (recursing into groups) together with
+/// the transform from the item's local coordinates to the coordinates of `frame`.
+pub fn walk_items<'a>(
+    frame: &'a Frame,
+    ts: Transform,
+    f: &mut impl FnMut(&'a FrameItem, Transform),
+) {
+    for (pos, item) in frame.items() {
+        let ts = ts.pre_concat(Transform::translate(pos.x, pos.y));
+        match item {
+            FrameItem::Group(group) => walk_items(&group.frame, ts.pre_concat(group.transform), f),
+            _ => f(item, ts),
+        }
+    }
+}
+
+/// An axis aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    pub fn width(&self) -> Abs {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> Abs {
+        self.max.y - self.min.y
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: Point::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Point::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /// Distance of `point` to the box in pt, zero inside of it.
+    pub fn distance(&self, point: Point) -> f64 {
+        let dx = (self.min.x - point.x)
+            .max(point.x - self.max.x)
+            .max(Abs::zero());
+        let dy = (self.min.y - point.y)
+            .max(point.y - self.max.y)
+            .max(Abs::zero());
+        dx.to_pt().hypot(dy.to_pt())
+    }
+
+    fn transformed(min: Point, max: Point, ts: Transform) -> Self {
+        let corners =
+            [min, Point::new(max.x, min.y), Point::new(min.x, max.y), max].map(|p| p.transform(ts));
+
+        corners[1..].iter().fold(
+            Self {
+                min: corners[0],
+                max: corners[0],
+            },
+            |bounds, p| bounds.union(Self { min: *p, max: *p }),
+        )
+    }
+}
+
+/// The bounding box of a leaf item after applying `ts`. Text is measured from the
+/// font's ascender to its descender.
+pub fn item_bounds(item: &FrameItem, ts: Transform) -> Option<Bounds> {
+    let (min, max) = match item {
+        FrameItem::Text(text) => {
+            let metrics = text.font.metrics();
+            (
+                Point::new(Abs::zero(), -metrics.ascender.at(text.size)),
+                Point::new(text.width(), -metrics.descender.at(text.size)),
+            )
+        }
+        FrameItem::Shape(shape, _) => {
+            let size = shape.geometry.bbox_size();
+            (Point::zero(), Point::new(size.x, size.y))
+        }
+        FrameItem::Image(_, size, _) => (Point::zero(), Point::new(size.x, size.y)),
+        _ => return None,
+    };
+
+    Some(Bounds::transformed(min, max, ts))
+}
+
+/// The source spans a leaf item was produced from.
+pub fn item_spans(item: &FrameItem) -> Vec<Span> {
+    match item {
+        FrameItem::Text(text) => {
+            let mut spans = text.glyphs.iter().map(|g| g.span.0).collect::<Vec<Span>>();
+            spans.dedup();
+            spans
+        }
+        FrameItem::Shape(_, span) | FrameItem::Image(_, _, span) => vec![*span],
+        _ => Vec::new(),
+    }
+}
+
+/// The span of the glyph closest to `point` horizontally, or the span of any other item.
+fn closest_span(item: &FrameItem, ts: Transform, point: Point) -> Option<Span> {
+    let FrameItem::Text(text) = item else {
+        return item_spans(item)
+            .into_iter()
+            .find(|span| !span.is_detached());
+    };
+
+    let local = ts
+        .invert()
+        .map_or(Abs::zero(), |inverse| point.transform(inverse).x);
+    let mut x = Abs::zero();
+    let mut closest: Option<(Abs, Span)> = None;
+    for glyph in &text.glyphs {
+        let width = glyph.x_advance.at(text.size);
+        let distance = (x + width / 2.0 - local).abs();
+        x += width;
+        if !glyph.span.0.is_detached() && closest.is_none_or(|(d, _)| distance < d) {
+            closest = Some((distance, glyph.span.0));
+        }
+    }
+    closest.map(|(_, span)| span)
+}
+
+/// The source span of the item nearest to `point`, in the coordinates of `frame`. Items
+/// without a span (like decorations added by the layout) are skipped.
+pub fn nearest_span(frame: &Frame, point: Point) -> Option<Span> {
+    let mut nearest: Option<(f64, Span)> = None;
+    walk_items(frame, Transform::identity(), &mut |item, ts| {
+        let Some(bounds) = item_bounds(item, ts) else {
+            return;
+        };
+        let distance = bounds.distance(point);
+        if nearest.is_some_and(|(d, _)| d <= distance) {
+            return;
+        }
+        if let Some(span) = closest_span(item, ts, point) {
+            nearest = Some((distance, span));
+        }
+    });
+    nearest.map(|(_, span)| span)
+}
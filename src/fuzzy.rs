@@ -0,0 +1,114 @@
+fn is_word_start(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()),
+    }
+}
+
+/// Higher is better, `None` if the characters of `pattern` don't appear in `label` in order.
+/// Matches at the start of the label or of a word and runs of consecutive characters score
+/// extra, skipped characters cost a little. Case is ignored.
+pub fn score(pattern: &str, label: &str) -> Option<i32> {
+    let pattern = pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut consecutive = false;
+    let mut prev = None;
+    for (i, c) in label.chars().enumerate() {
+        if next == pattern.len() {
+            break;
+        }
+        if c.to_lowercase().next() == Some(pattern[next]) {
+            score += 1;
+            if i == 0 {
+                score += 20;
+            } else if is_word_start(prev, c) {
+                score += 10;
+            }
+            if consecutive {
+                score += 5;
+            }
+            consecutive = true;
+            next += 1;
+        } else {
+            score -= 1;
+            consecutive = false;
+        }
+        prev = Some(c);
+    }
+
+    (next == pattern.len()).then_some(score)
+}
+
+/// The items whose label matches `pattern`, best first and at most `limit` of them. Equal
+/// scores keep their order, without a pattern only the limit applies.
+pub fn filter<T>(
+    items: Vec<T>,
+    pattern: Option<&str>,
+    limit: Option<usize>,
+    label: impl Fn(&T) -> &str,
+) -> Vec<T> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let Some(pattern) = pattern.filter(|p| !p.is_empty()) else {
+        return items.into_iter().take(limit).collect();
+    };
+
+    let mut matches = items
+        .into_iter()
+        .filter_map(|item| Some((score(pattern, label(&item))?, item)))
+        .collect::<Vec<_>>();
+    // Stable, so ties stay in their original order
+    matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// How `label` matches `pattern`, lower is better: a prefix, the start of a later word, or
+/// any subsequence. Case is ignored.
+pub fn tier(pattern: &str, label: &str) -> Option<u8> {
+    let pattern = pattern.to_lowercase();
+    let lower = label.to_lowercase();
+    if lower.starts_with(&pattern) {
+        return Some(0);
+    }
+
+    let mut prev = None;
+    for (i, c) in label.char_indices() {
+        if i > 0 && is_word_start(prev, c) && label[i..].to_lowercase().starts_with(&pattern) {
+            return Some(1);
+        }
+        prev = Some(c);
+    }
+
+    score(&pattern, label).map(|_| 2)
+}
+
+/// The items matching `pattern` ordered by `tier` and at most `limit` of them, together
+/// with the number of matches before the limit. Items of the same tier keep their order.
+pub fn rank<T>(
+    items: Vec<T>,
+    pattern: &str,
+    limit: usize,
+    label: impl Fn(&T) -> &str,
+) -> (Vec<T>, usize) {
+    let mut matches = items
+        .into_iter()
+        .filter_map(|item| Some((tier(pattern, label(&item))?, item)))
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|(tier, _)| *tier);
+
+    let total = matches.len();
+    let items = matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect();
+    (items, total)
+}
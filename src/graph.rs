@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use typst::syntax::FileId;
+
+use crate::{
+    file_entry::FileEntry,
+    js_types::{ImportCycle, ImportEdges, ImportGraph},
+    paths,
+};
+
+/// Rooted paths of the project files each project file imports or includes.
+pub type Edges = BTreeMap<String, BTreeSet<String>>;
+
+fn rooted(id: FileId) -> String {
+    id.vpath().as_rooted_path().to_string_lossy().into_owned()
+}
+
+/// The import graph of the project files among `sources`, read from their syntax trees.
+pub fn import_edges<'a>(sources: impl Iterator<Item = (&'a FileId, &'a FileEntry)>) -> Edges {
+    sources
+        .filter(|(id, entry)| id.package().is_none() && entry.is_utf8())
+        .map(|(id, entry)| {
+            let imports = paths::module_references(&entry.source)
+                .iter()
+                .map(|reference| {
+                    reference
+                        .resolve(*id)
+                        .as_rooted_path()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            (rooted(*id), imports)
+        })
+        .collect()
+}
+
+/// Extends `stack` with every simple path back to its first file that only passes through
+/// files sorting after it, so each cycle is found from its smallest file alone.
+fn find_cycles(edges: &Edges, stack: &mut Vec<String>, cycles: &mut Vec<Vec<String>>) {
+    let start = stack[0].clone();
+    let last = stack[stack.len() - 1].clone();
+    for next in edges.get(&last).into_iter().flatten() {
+        if *next == start {
+            cycles.push(stack.clone());
+        } else if *next > start && !stack.contains(next) {
+            stack.push(next.clone());
+            find_cycles(edges, stack, cycles);
+            stack.pop();
+        }
+    }
+}
+
+/// Every elementary cycle of imports, each listed once starting at its smallest path.
+/// Overlapping cycles like `a -> b -> c -> a` and `a -> c -> a` are reported separately.
+pub fn cycles(edges: &Edges) -> Vec<Vec<String>> {
+    let mut cycles = Vec::new();
+    for path in edges.keys() {
+        find_cycles(edges, &mut vec![path.clone()], &mut cycles);
+    }
+    cycles.sort();
+    cycles
+}
+
+pub fn import_graph(edges: &Edges) -> ImportGraph {
+    ImportGraph {
+        files: edges
+            .iter()
+            .map(|(path, imports)| ImportEdges {
+                path: path.clone(),
+                imports: imports.iter().cloned().collect(),
+            })
+            .collect(),
+        cycles: cycles(edges)
+            .into_iter()
+            .map(|files| ImportCycle { files })
+            .collect(),
+    }
+}
+
+/// The files importing `path` directly or through other files, sorted.
+pub fn dependents(edges: &Edges, path: &str) -> Vec<String> {
+    let mut importers: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (from, imports) in edges {
+        for to in imports {
+            importers.entry(to).or_default().push(from);
+        }
+    }
+
+    let mut found = BTreeSet::new();
+    let mut queue = vec![path];
+    while let Some(current) = queue.pop() {
+        for from in importers.get(current).into_iter().flatten() {
+            if *from != path && found.insert(from.to_string()) {
+                queue.push(from);
+            }
+        }
+    }
+    found.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(pairs: &[(&str, &str)]) -> Edges {
+        let mut edges = Edges::new();
+        for (from, to) in pairs {
+            edges
+                .entry(from.to_string())
+                .or_default()
+                .insert(to.to_string());
+            edges.entry(to.to_string()).or_default();
+        }
+        edges
+    }
+
+    #[test]
+    fn overlapping_cycles() {
+        let edges = edges(&[("/a", "/b"), ("/b", "/c"), ("/c", "/a"), ("/a", "/c")]);
+        assert_eq!(
+            cycles(&edges),
+            vec![vec!["/a", "/b", "/c"], vec!["/a", "/c"]]
+        );
+    }
+
+    #[test]
+    fn cycles_sharing_a_file() {
+        // `/b` lies on both cycles, the second one is entered from a file already visited
+        let edges = edges(&[
+            ("/a", "/b"),
+            ("/b", "/a"),
+            ("/b", "/c"),
+            ("/c", "/d"),
+            ("/d", "/b"),
+            ("/main", "/a"),
+        ]);
+        assert_eq!(
+            cycles(&edges),
+            vec![vec!["/a", "/b"], vec!["/b", "/c", "/d"]]
+        );
+    }
+
+    #[test]
+    fn self_import_and_acyclic() {
+        assert_eq!(cycles(&edges(&[("/a", "/a")])), vec![vec!["/a"]]);
+        assert!(cycles(&edges(&[("/a", "/b"), ("/b", "/c"), ("/a", "/c")])).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use typst::syntax::{highlight, LinkedNode, Source, SyntaxKind, Tag};
+
+use crate::js_types::SemanticToken;
+
+fn token_type(tag: Tag) -> &'static str {
+    match tag {
+        Tag::Comment => "comment",
+        Tag::Punctuation => "punctuation",
+        Tag::Escape => "escape",
+        Tag::Strong => "strong",
+        Tag::Emph => "emph",
+        Tag::Link => "link",
+        Tag::Raw => "raw",
+        Tag::Label => "label",
+        Tag::Ref => "ref",
+        Tag::Heading => "heading",
+        Tag::ListMarker => "marker",
+        Tag::ListTerm => "term",
+        Tag::MathDelimiter => "delimiter",
+        Tag::MathOperator => "operator",
+        Tag::Keyword => "keyword",
+        Tag::Operator => "operator",
+        Tag::Number => "number",
+        Tag::String => "string",
+        Tag::Function => "function",
+        Tag::Interpolated => "variable",
+        Tag::Error => "error",
+    }
+}
+
+#[derive(Clone, Default)]
+struct Context {
+    tag: Option<Tag>, // Nearest highlighted ancestor
+    math: bool,
+    strong: bool,
+    emph: bool,
+}
+
+struct Encoder<'a> {
+    source: &'a Source,
+    tokens: Vec<SemanticToken>,
+    prev_line: usize,
+    prev_start: usize,
+}
+
+impl Encoder<'_> {
+    fn push(&mut self, start: usize, end: usize, tag: Tag, ctx: &Context) {
+        let Some(line) = self.source.byte_to_line(start) else {
+            return;
+        };
+        let Some(line_start) = self.source.line_to_byte(line) else {
+            return;
+        };
+
+        let utf16 = |offset: usize| self.source.byte_to_utf16(offset).unwrap_or(0);
+        let start_char = utf16(start) - utf16(line_start);
+        let length = utf16(end) - utf16(start);
+
+        let mut modifiers = Vec::new();
+        if ctx.math {
+            modifiers.push("math".to_string());
+        }
+        if ctx.strong && tag != Tag::Strong {
+            modifiers.push("strong".to_string());
+        }
+        if ctx.emph && tag != Tag::Emph {
+            modifiers.push("emph".to_string());
+        }
+
+        let delta_line = line - self.prev_line;
+        let delta_start_char = if delta_line == 0 {
+            start_char - self.prev_start
+        } else {
+            start_char
+        };
+        self.prev_line = line;
+        self.prev_start = start_char;
+
+        self.tokens.push(SemanticToken {
+            line,
+            start_char,
+            length,
+            token_type: token_type(tag).to_string(),
+            modifiers,
+            delta_line,
+            delta_start_char,
+        });
+    }
+
+    fn leaf(&mut self, node: &LinkedNode, tag: Tag, ctx: &Context) {
+        // Tokens may not span lines, so multi-line leaves (raw blocks, comments) are split
+        let mut offset = node.offset();
+        for line in node.text().split_inclusive('\n') {
+            let content = line.trim_end_matches(['\n', '\r']);
+            if !content.trim().is_empty() {
+                self.push(offset, offset + content.len(), tag, ctx);
+            }
+            offset += line.len();
+        }
+    }
+
+    fn walk(&mut self, node: &LinkedNode, parent: &Context) {
+        let mut ctx = parent.clone();
+        match node.kind() {
+            SyntaxKind::Equation => ctx.math = true,
+            SyntaxKind::Strong => ctx.strong = true,
+            SyntaxKind::Emph => ctx.emph = true,
+            _ => {}
+        }
+        if let Some(tag) = highlight(node) {
+            if tag != Tag::Error || node.get().children().len() == 0 {
+                ctx.tag = Some(tag);
+            }
+        }
+
+        if node.get().children().len() == 0 {
+            if let Some(tag) = ctx.tag {
+                self.leaf(node, tag, &ctx);
+            }
+            return;
+        }
+
+        for child in node.children() {
+            self.walk(&child, &ctx);
+        }
+    }
+}
+
+/// Semantic tokens of a source in document order, using typst's own highlighter.
+/// Each leaf gets the tag of its nearest highlighted ancestor.
+pub fn semantic_tokens(source: &Source) -> Vec<SemanticToken> {
+    let mut encoder = Encoder {
+        source,
+        tokens: Vec::new(),
+        prev_line: 0,
+        prev_start: 0,
+    };
+    encoder.walk(&LinkedNode::new(source.root()), &Context::default());
+    encoder.tokens
+}
@@ -1,14 +1,19 @@
 use std::collections::HashMap;
 
 use chrono::offset;
-use serde::Serialize;
-use typst::syntax::{FileId, LinkedNode, Source, Span, SyntaxKind};
+use serde::{Deserialize, Serialize};
+use typst::{
+    syntax::{FileId, LinkedNode, Source, Span, SyntaxKind},
+    Library,
+};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 use crate::{
     file_entry::FileEntry,
-    logWasm,
-    tidy::{collect_tidy_doc, parse_doc_str},
+    limits,
+    logging::{self, LogLevel},
+    quickfix,
+    tidy::{builtin_docs, collect_tidy_doc, markdown_to_html, parse_doc_str},
 };
 
 #[macro_export]
@@ -27,23 +32,61 @@ macro_rules! define_lowercase_enum {
 pub struct ResolvedSpan {
     pub span: String,
     pub file_path: String,
-    pub start_offset: usize,
+    pub start_offset: usize, // In UTF-8 bytes, like all offsets typst uses
     pub end_offset: usize,
+    pub start_utf16: usize, // The offsets in UTF-16 code units, for indexing JS strings
+    pub end_utf16: usize,
+    pub start_line: usize,   // Zero-based
+    pub start_column: usize, // Zero-based, in UTF-16 code units like JS strings
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// Zero-based line and UTF-16 column of a byte offset.
+fn line_column(source: &Source, offset: usize) -> (usize, usize) {
+    let line = source.byte_to_line(offset).unwrap_or(0);
+    let line_start = source.line_to_byte(line).unwrap_or(0);
+    let column = source
+        .byte_to_utf16(offset)
+        .zip(source.byte_to_utf16(line_start))
+        .map_or(0, |(offset, line_start)| offset - line_start);
+    (line, column)
 }
 
 impl ResolvedSpan {
+    pub fn detached(span: Span) -> Self {
+        Self {
+            span: format!("{:?}", span),
+            file_path: String::new(),
+            start_offset: 0,
+            end_offset: 0,
+            start_utf16: 0,
+            end_utf16: 0,
+            start_line: 0,
+            start_column: 0,
+            end_line: 0,
+            end_column: 0,
+        }
+    }
+
+    /// Falls back to `detached` with a warning when the span does not point into `source`,
+    /// error reporting must never panic.
     pub fn from_source(span: Span, source: &Source) -> Self {
         if span.is_detached() {
-            Self {
-                span: format!("{:?}", span),
-                file_path: String::new(),
-                start_offset: 0,
-                end_offset: 0,
-            }
+            Self::detached(span)
         } else {
-            let range = source
-                .range(span)
-                .expect("Range should point to the source file. Looks like it does not.");
+            let Some(range) = source.range(span) else {
+                logging::log(LogLevel::Warn, || {
+                    format!(
+                        "span {:?} does not point into {:?}, reporting it detached",
+                        span,
+                        source.id().vpath()
+                    )
+                });
+                return Self::detached(span);
+            };
+            let (start_line, start_column) = line_column(source, range.start);
+            let (end_line, end_column) = line_column(source, range.end);
 
             Self {
                 span: format!("{:?}", span),
@@ -51,46 +94,33 @@ impl ResolvedSpan {
                     .id()
                     .vpath()
                     .as_rooted_path()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
+                    .to_string_lossy()
+                    .into_owned(),
                 start_offset: range.start,
                 end_offset: range.end,
+                start_utf16: source.byte_to_utf16(range.start).unwrap_or(0),
+                end_utf16: source.byte_to_utf16(range.end).unwrap_or(0),
+                start_line,
+                start_column,
+                end_line,
+                end_column,
             }
         }
     }
 
     pub fn from_sources(span: Span, sources: &HashMap<FileId, FileEntry>) -> Self {
         if span.is_detached() {
-            Self {
-                span: format!("{:?}", span),
-                file_path: String::new(),
-                start_offset: 0,
-                end_offset: 0,
-            }
+            Self::detached(span)
         } else {
-            let file_id = span.id().expect("None detached span should have an id");
-
-            let entry = sources
-                .get(&file_id)
-                .expect("File should exist because it got compiled");
-
-            let source = entry.source();
-
-            let range = source
-                .range(span)
-                .expect("Range should be valid because the span points to the file");
-
-            Self {
-                span: format!("{:?}", span),
-                file_path: file_id
-                    .vpath()
-                    .as_rooted_path()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-                start_offset: range.start,
-                end_offset: range.end,
+            // Files that are not loaded (e.g. packages that were not fetched) cannot be resolved
+            match span.id().and_then(|id| sources.get(&id)) {
+                Some(entry) => Self::from_source(span, &entry.source),
+                None => {
+                    logging::log(LogLevel::Warn, || {
+                        format!("span {:?} lies in a file that is not loaded", span)
+                    });
+                    Self::detached(span)
+                }
             }
         }
     }
@@ -124,6 +154,37 @@ pub struct Diagnostics {
     pub root: ResolvedSpan,
     pub hints: Vec<String>,
     pub trace: Vec<ResolvedSpan>,
+    pub trace_points: Vec<TracePoint>, // `trace` with descriptions, innermost first
+    pub promoted: bool, // A warning that failed a strict compile, `severity` stays warning
+    pub fixes: Vec<QuickFix>, // Best effort, edits apply to project files only
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct QuickFix {
+    pub title: String, // Like "Replace with `align`"
+    pub edits: Vec<FileTextEdit>,
+}
+
+#[wasm_bindgen]
+impl QuickFix {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct TracePoint {
+    pub description: String, // Like "error occurred in this show rule"
+    pub span: ResolvedSpan,
+}
+
+#[wasm_bindgen]
+impl TracePoint {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
 }
 
 #[wasm_bindgen]
@@ -134,9 +195,42 @@ impl Diagnostics {
 }
 
 impl Diagnostics {
+    /// Returned alone when a compile was cancelled, instead of its partial output.
+    pub fn cancelled() -> Self {
+        Self::error("Cancelled: compilation was cancelled".to_string())
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            root: ResolvedSpan::detached(Span::detached()),
+            hints: Vec::new(),
+            trace: Vec::new(),
+            trace_points: Vec::new(),
+            promoted: false,
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn from_syntax_error(err: typst::syntax::SyntaxError, source: &Source) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: err.message.to_string(),
+            root: ResolvedSpan::from_source(err.span, source),
+            hints: err.hints.iter().map(|hint| hint.to_string()).collect(),
+            trace: Vec::new(),
+            trace_points: Vec::new(),
+            promoted: false,
+            fixes: quickfix::fixes(&err.message, err.span, source, None),
+        }
+    }
+
+    /// `library` is used to suggest names for unknown variables.
     pub fn from_diag(
         err: typst::diag::SourceDiagnostic,
         sources: HashMap<FileId, FileEntry>,
+        library: &Library,
     ) -> Self {
         let severity = Severity::from(err.severity);
         let message = err.message.to_string();
@@ -147,22 +241,59 @@ impl Diagnostics {
 
         let root = ResolvedSpan::from_sources(span, &sources);
 
-        let trace = err
+        let trace_points = err
             .trace
             .iter()
-            .map(|span| ResolvedSpan::from_sources(span.span, &sources))
+            .map(|point| TracePoint {
+                description: point.v.to_string(),
+                span: ResolvedSpan::from_sources(point.span, &sources),
+            })
+            .collect::<Vec<_>>();
+        let trace = trace_points
+            .iter()
+            .map(|point| point.span.clone())
             .collect();
 
+        // Package files can't be edited by the frontend
+        let fixes = span
+            .id()
+            .filter(|id| id.package().is_none())
+            .and_then(|id| sources.get(&id))
+            .map(|entry| quickfix::fixes(&message, span, &entry.source, Some(library)))
+            .unwrap_or_default();
+
         Self {
             severity,
             message,
             root,
             hints,
             trace,
+            trace_points,
+            promoted: false,
+            fixes,
         }
     }
 }
 
+/*
+ * Output Formats
+ */
+
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub enum OutputFormat {
+    Objects = "objects",
+    JsonString = "json_string",
+    MsgpackBytes = "msgpack_bytes", // Uint8Array of MessagePack with named fields
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq)]
+pub enum Target {
+    Paged = "paged",
+    Html = "html", // Needs typst 0.13, every compile fails with `Unsupported`
+}
+
 /*
  * Completion
  */
@@ -217,7 +348,7 @@ impl From<typst_ide::CompletionKind> for CompletionDetail {
 }
 
 #[wasm_bindgen(getter_with_clone)]
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct Completion {
     pub kind: CompletionDetail,
     pub label: String,
@@ -243,6 +374,74 @@ impl From<typst_ide::Completion> for Completion {
     }
 }
 
+/// Completions with the byte range of the partly typed text they replace.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct CompletionResult {
+    pub from: usize, // Start of the typed text
+    pub to: usize,   // The requested offset
+    pub completions: Vec<Completion>,
+}
+
+#[wasm_bindgen]
+impl CompletionResult {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct FilteredCompletions {
+    pub from: usize, // Replaced range, as in `CompletionResult`
+    pub to: usize,
+    pub total: usize, // Matches before the limit was applied
+    pub completions: Vec<Completion>,
+}
+
+#[wasm_bindgen]
+impl FilteredCompletions {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Labels
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct LabelInfo {
+    pub name: String, // Without the angle brackets
+    pub file_path: String,
+    pub span: ResolvedSpan,
+    pub description: Option<String>, // Heading text, figure caption or the element's name
+    pub duplicate: bool,             // Whether another label with the same name exists
+}
+
+#[wasm_bindgen]
+impl LabelInfo {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct Reference {
+    pub span: ResolvedSpan,
+    pub is_definition: bool,
+    pub package: Option<String>, // Spec like "@preview/cetz:0.3.1" when inside a package
+}
+
+#[wasm_bindgen]
+impl Reference {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
 /*
  * Definition
  */
@@ -255,11 +454,20 @@ pub struct Value {
     pub docs: Option<String>,
 }
 
-impl From<typst::foundations::Value> for Value {
-    fn from(value: typst::foundations::Value) -> Self {
+impl Value {
+    pub fn new(value: typst::foundations::Value, options: &DocOptions) -> Self {
+        // Builtin functions get the same tidy docs as user-defined ones
+        let builtin = match &value {
+            typst::foundations::Value::Func(func) => func
+                .name()
+                .and_then(|name| builtin_docs(name.to_string(), func))
+                .map(|docs| docs.to_doc_string(options)),
+            _ => None,
+        };
+
         Self {
             name: value.name().map(|name| name.to_string()),
-            docs: value.docs().map(|docs| docs.to_string()),
+            docs: builtin.or_else(|| value.docs().map(|docs| docs.to_string())),
             display: value.display().plain_text().to_string(),
         }
     }
@@ -303,12 +511,16 @@ impl Definition {
 }
 
 impl Definition {
-    pub fn new(definition: typst_ide::Definition, sources: HashMap<FileId, FileEntry>) -> Self {
+    pub fn new(
+        definition: typst_ide::Definition,
+        sources: HashMap<FileId, FileEntry>,
+        options: &DocOptions,
+    ) -> Self {
         let name = definition.name.to_string();
         let span = ResolvedSpan::from_sources(definition.span, &sources);
         let name_span = ResolvedSpan::from_sources(definition.name_span, &sources);
         let kind = DefinitionKind::from(definition.kind.clone());
-        let mut value = definition.value.map(Value::from);
+        let mut value = definition.value.map(|value| Value::new(value, options));
 
         if (value.is_none() || value.as_ref().unwrap().docs.is_none())
             && (definition.kind == typst_ide::DefinitionKind::Function
@@ -316,25 +528,22 @@ impl Definition {
         {
             let target = definition.name_span;
 
-            if !target.is_detached() {
-                let file_id = target.id().expect("None detached span should have an id");
-
-                let entry = sources
-                    .get(&file_id)
-                    .expect("File should exist because it got compiled");
+            let node = target
+                .id()
+                .and_then(|file_id| sources.get(&file_id))
+                .and_then(|entry| Some(entry.source.find(target)?.parent()?.clone()));
 
-                let source = entry.source();
-
-                let node = source.find(target).unwrap().parent().unwrap().clone();
+            if let Some(node) = node {
                 let collected = collect_tidy_doc(node);
 
-                logWasm(&format!("Collected: {:?}", collected));
+                logging::log(LogLevel::Trace, || format!("Collected: {:?}", collected));
 
                 value = Some(Value {
                     name: Some(name.clone()),
                     display: name.clone(),
                     docs: Some(
-                        parse_doc_str(definition.name.to_string(), collected).to_doc_string(),
+                        parse_doc_str(definition.name.to_string(), collected)
+                            .to_doc_string(options),
                     ),
                 });
             }
@@ -437,6 +646,20 @@ impl RawPackageSpec {
     }
 }
 
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct PrefetchReport {
+    pub downloaded: Vec<String>, // Specs like "@preview/cetz:0.3.1"
+    pub failed: Vec<String>,     // "<spec>: <reason>", also reported as diagnostics on compile
+}
+
+#[wasm_bindgen]
+impl PrefetchReport {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
 #[wasm_bindgen(getter_with_clone)]
 #[derive(Clone, Serialize)]
 pub struct AstNode {
@@ -481,86 +704,704 @@ impl AstNode {
     }
 }
 
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct FileTextEdit {
+    pub file_path: String,
+    pub start_offset: usize, // Byte offsets into the file's text before the edit
+    pub end_offset: usize,
+    pub insert_text: String,
+}
+
+#[wasm_bindgen]
+impl FileTextEdit {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
 /*
- * Tidy Docs
+ * Directories
  */
 
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct DirEntry {
+    pub name: String,
+    pub path: String, // Rooted, package files live under "/@namespace/name:version/"
+    pub is_dir: bool,
+    pub is_package: bool,
+}
+
 #[wasm_bindgen]
-#[derive(Clone, Serialize, Debug)]
-pub enum TidyType {
-    Function,
-    Variable,
+impl DirEntry {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
 }
 
-#[derive(Clone, Serialize, Debug)]
-pub struct TidyComments {
-    pub pre: String,     // Comments before the function/variable
-    pub type_: TidyType, // The type of the function/variable
-    pub args: Vec<(String, String, Option<String>)>, // Comments before the arguments of th function
+/*
+ * Resource Limits
+ */
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct ResourceLimits {
+    pub max_file_size: Option<usize>, // Bytes of a single project file
+    pub max_project_size: Option<usize>, // Bytes of all project files together
+    pub max_archive_entries: Option<usize>, // Files in a single package archive
+    pub max_archive_size: Option<usize>, // Decompressed bytes of a single package archive
+    pub max_compile_ms: Option<usize>, // Wall time of a compile, checked at file loads and between pages
 }
 
-impl TidyComments {
-    pub fn new(pre: String) -> Self {
+impl Default for ResourceLimits {
+    fn default() -> Self {
         Self {
-            pre,
-            type_: TidyType::Function,
-            args: Vec::new(),
+            max_file_size: Some(limits::DEFAULT_MAX_FILE_SIZE),
+            max_project_size: None,
+            max_archive_entries: None,
+            max_archive_size: Some(limits::DEFAULT_MAX_ARCHIVE_SIZE),
+            max_compile_ms: None,
         }
     }
+}
 
-    pub fn add_arg(&mut self, arg: String, doc: String, default: Option<String>) {
-        self.args.push((arg, doc, default));
+#[wasm_bindgen]
+impl ResourceLimits {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn set_type(&mut self, type_: TidyType) {
-        self.type_ = type_;
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
     }
+}
 
-    pub fn has_args(&self) -> bool {
-        !self.args.is_empty()
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct ResourceUsage {
+    pub project_files: usize,
+    pub project_size: usize,
+    pub largest_file: usize,
+    pub package_files: usize,
+    pub package_size: usize,
+}
+
+#[wasm_bindgen]
+impl ResourceUsage {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
     }
 }
 
+/*
+ * Semantic Tokens
+ */
+
 #[wasm_bindgen(getter_with_clone)]
 #[derive(Clone, Serialize)]
-pub struct TidyDocs {
-    pub name: String,
-    pub type_: TidyType,
-    pub description: Option<String>,
-    pub return_types: Vec<String>,
-    pub arguments: Vec<TidyArgDocs>,
+pub struct SemanticToken {
+    pub line: usize,
+    pub start_char: usize, // UTF-16 code units from the start of the line
+    pub length: usize,     // UTF-16 code units
+    pub token_type: String,
+    pub modifiers: Vec<String>,
+    pub delta_line: usize, // LSP style deltas relative to the previous token
+    pub delta_start_char: usize,
 }
 
-impl TidyDocs {
-    pub fn new(name: String, type_: TidyType) -> Self {
-        Self {
-            name,
-            type_,
-            description: None,
-            return_types: Vec::new(),
-            arguments: Vec::new(),
-        }
+#[wasm_bindgen]
+impl SemanticToken {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
     }
+}
 
-    pub fn add_description(&mut self, description: String) {
-        self.description = Some(description);
+/*
+ * Folding Ranges
+ */
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Serialize)]
+pub enum FoldingKind {
+    Code = "code",
+    Content = "content",
+    Args = "args",
+    Heading = "heading",
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct FoldingRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub kind: FoldingKind,
+}
+
+#[wasm_bindgen]
+impl FoldingRange {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
     }
+}
 
-    pub fn add_return_type(&mut self, return_type: String) {
-        self.return_types.push(return_type);
+/*
+ * Text Edits
+ */
+
+#[wasm_bindgen]
+#[derive(Clone, Debug, Serialize)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[wasm_bindgen]
+impl TextRange {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
     }
+}
 
-    pub fn add_argument(&mut self, arg: TidyArgDocs) {
-        self.arguments.push(arg);
+impl From<std::ops::Range<usize>> for TextRange {
+    fn from(range: std::ops::Range<usize>) -> Self {
+        Self {
+            start: range.start,
+            end: range.end,
+        }
     }
+}
 
-    pub fn to_doc_string(&self) -> String {
-        let mut result = String::new();
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, Serialize)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[wasm_bindgen]
+impl TextEdit {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct FormatResult {
+    pub text: String,         // Formatted text, or the unchanged text when skipped
+    pub edits: Vec<TextEdit>, // Turning the current text into `text`, ascending
+    pub skipped: bool,        // Whether syntax errors prevented formatting
+    pub message: Option<String>,
+}
+
+#[wasm_bindgen]
+impl FormatResult {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct DiffResult {
+    pub edits: Vec<TextEdit>, // Relative to the previous text, ascending and non-overlapping
+    pub range: Option<TextRange>, // Combined reparsed range in the new text
+}
+
+#[wasm_bindgen]
+impl DiffResult {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Rendering
+ */
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct RenderOptions {
+    pub merged: bool,         // All pages in a single SVG
+    pub gap_pt: f64,          // Space around and between merged pages
+    pub transparent: bool,    // Pages without an explicit fill get no white background
+    pub annotate_spans: bool, // `data-span="path:start:end"` on text and shapes, about doubles the size
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            merged: false,
+            gap_pt: typst::layout::Abs::cm(2.0).to_pt(),
+            transparent: false,
+            annotate_spans: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl RenderOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/// Styling of the preview set by `set_render_options`, the compiled document is untouched.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewOptions {
+    pub page_fill: Option<String>, // Hex color or "transparent", replaces the fill of every page
+    pub text_invert: bool,         // Inverts the lightness of solid text colors
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct RenderedPages {
+    pub start: usize, // Actually rendered range after clamping, end exclusive
+    pub end: usize,
+    pub pages: Vec<String>, // One SVG per page, or a single one when merged
+}
+
+#[wasm_bindgen]
+impl RenderedPages {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ChangedPages {
+    pub pages: Vec<String>, // Like `compile_with`, skipped pages are empty strings
+    pub changed: Vec<usize>, // Indices of pages that differ from the previous compile
+}
+
+#[wasm_bindgen]
+impl ChangedPages {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct PageSize {
+    pub width_pt: f64,
+    pub height_pt: f64,
+}
+
+#[wasm_bindgen]
+impl PageSize {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct PreviewPosition {
+    pub page: usize, // 0-based page index
+    pub x_pt: f64,   // From the top left corner of the page
+    pub y_pt: f64,
+}
+
+#[wasm_bindgen]
+impl PreviewPosition {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Accessibility
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct EquationAltText {
+    pub page: usize, // 0-based page index
+    pub x_pt: f64,   // Bounding box on the page, in pt from the top left corner
+    pub y_pt: f64,
+    pub width_pt: f64,
+    pub height_pt: f64,
+    pub block: bool,
+    pub source_text: String, // Math source without the dollars and with collapsed whitespace
+    pub speech: String,
+    pub unsupported: bool, // Whether the speech contains "[unsupported: ...]" markers
+    pub span: ResolvedSpan,
+}
+
+#[wasm_bindgen]
+impl EquationAltText {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Document Statistics
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct DocumentStats {
+    pub words: usize, // CJK characters count as one word each
+    pub characters: usize,
+    pub characters_no_spaces: usize,
+    pub pages: usize,
+    pub paragraphs: usize, // Estimated from the vertical gaps between lines
+}
+
+#[wasm_bindgen]
+impl DocumentStats {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct TextRun {
+    pub text: String,       // Words hyphenated at a line break are joined again
+    pub page: usize,        // 0-based page index
+    pub span: ResolvedSpan, // Of the markup text the run was laid out from
+}
+
+#[wasm_bindgen]
+impl TextRun {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Font Usage
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct UsedFont {
+    pub index: Option<usize>, // Position in the loaded fonts, as typst's `World::font` sees it
+    pub family: String,
+    pub style: String,        // "normal", "italic" or "oblique"
+    pub weight: u16,          // 100 to 900, 400 is regular
+    pub stretch: f64,         // 1.0 is normal width
+    pub glyphs: usize,        // Laid out with this font across the document
+    pub notdef_glyphs: usize, // Missing glyphs drawn as boxes (tofu)
+}
+
+#[wasm_bindgen]
+impl UsedFont {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct FontUsage {
+    pub used: Vec<UsedFont>,
+    pub missing: Vec<String>, // Requested families that are not loaded, sorted
+}
+
+#[wasm_bindgen]
+impl FontUsage {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Symbols
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct SymbolInfo {
+    pub name: String, // Dotted path like "sym.arrow.r"
+    pub char: String,
+    pub markup_shorthand: Option<String>, // Like "--" for an en dash
+    pub math_shorthand: Option<String>,   // Like "->" for sym.arrow.r
+}
+
+#[wasm_bindgen]
+impl SymbolInfo {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct LibrarySymbol {
+    pub name: String,
+    pub kind: String,            // "func", "type", "module" or "value"
+    pub summary: Option<String>, // First sentence of the docs
+    pub params: Vec<String>,     // Parameter names of functions and type constructors
+    pub docs: Option<TidyDocs>,  // Same shape as hover docs
+}
+
+#[wasm_bindgen]
+impl LibrarySymbol {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Imports
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ImportInfo {
+    pub source: String, // The path or package as written, or the imported expression
+    pub package: Option<String>, // Like "@preview/cetz:0.3.1"
+    pub package_spec: Option<RawPackageSpec>, // The components of `package`
+    pub registered: bool, // Whether `package` is registered, always false for project files
+    pub resolved: Option<String>, // Rooted path of the imported project file, if it exists
+    pub names: Vec<String>, // Imported items as written (`a`, `b as c`), or "*"
+    pub new_name: Option<String>, // Set by `import "..." as name`
+    pub span: ResolvedSpan,
+}
+
+#[wasm_bindgen]
+impl ImportInfo {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ImportEdges {
+    pub path: String,         // Rooted path of a project file
+    pub imports: Vec<String>, // Files it imports or includes, sorted, missing ones too
+}
+
+#[wasm_bindgen]
+impl ImportEdges {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ImportCycle {
+    pub files: Vec<String>, // Each file imports the next, the last one the first
+}
+
+#[wasm_bindgen]
+impl ImportCycle {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ImportGraph {
+    pub files: Vec<ImportEdges>, // Sorted by path
+    pub cycles: Vec<ImportCycle>,
+}
+
+#[wasm_bindgen]
+impl ImportGraph {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Exports
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ExportedSymbol {
+    pub name: String,
+    pub kind: TidyType,
+    pub docs: Option<TidyDocs>,
+    pub module_scope: bool, // Whether `import` brings it in, false inside blocks and closures
+    pub span: ResolvedSpan, // Of the bound name
+}
+
+#[wasm_bindgen]
+impl ExportedSymbol {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Profiling
+ */
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ProfilePhase {
+    pub name: String, // Like "compile", "svg page 3" or "fetch @preview/cetz:0.3.1"
+    pub ms: f64,
+}
+
+#[wasm_bindgen]
+impl ProfilePhase {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct ProfileReport {
+    pub phases: Vec<ProfilePhase>, // In the order they ran
+    pub total_ms: f64,             // Of the compile, package fetches are not included
+}
+
+#[wasm_bindgen]
+impl ProfileReport {
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/*
+ * Tidy Docs
+ */
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize)]
+pub struct DocOptions {
+    pub markdown: bool, // Render descriptions as markdown instead of plain text
+}
+
+impl Default for DocOptions {
+    fn default() -> Self {
+        Self { markdown: true }
+    }
+}
+
+#[wasm_bindgen]
+impl DocOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn to_json(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self).unwrap()
+    }
+}
+
+/// Escapes text interpolated into the generated documentation markup.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Serialize, Debug)]
+pub enum TidyType {
+    Function,
+    Variable,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct TidyComments {
+    pub pre: String,     // Comments before the function/variable
+    pub type_: TidyType, // The type of the function/variable
+    pub args: Vec<(String, String, Option<String>)>, // Comments before the arguments of th function
+}
+
+impl TidyComments {
+    pub fn new(pre: String) -> Self {
+        Self {
+            pre,
+            type_: TidyType::Function,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn add_arg(&mut self, arg: String, doc: String, default: Option<String>) {
+        self.args.push((arg, doc, default));
+    }
+
+    pub fn set_type(&mut self, type_: TidyType) {
+        self.type_ = type_;
+    }
+
+    pub fn has_args(&self) -> bool {
+        !self.args.is_empty()
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Serialize)]
+pub struct TidyDocs {
+    pub name: String,
+    pub type_: TidyType,
+    pub description: Option<String>,
+    pub return_types: Vec<String>,
+    pub arguments: Vec<TidyArgDocs>,
+}
+
+impl TidyDocs {
+    pub fn new(name: String, type_: TidyType) -> Self {
+        Self {
+            name,
+            type_,
+            description: None,
+            return_types: Vec::new(),
+            arguments: Vec::new(),
+        }
+    }
+
+    pub fn add_description(&mut self, description: String) {
+        self.description = Some(description);
+    }
+
+    pub fn add_return_type(&mut self, return_type: String) {
+        self.return_types.push(return_type);
+    }
+
+    pub fn add_argument(&mut self, arg: TidyArgDocs) {
+        self.arguments.push(arg);
+    }
+
+    pub fn to_doc_string(&self, options: &DocOptions) -> String {
+        let mut result = String::new();
+
+        let format_text = |text: &str| {
+            if options.markdown {
+                markdown_to_html(text)
+            } else {
+                escape_html(&text.replace("\n", ""))
+            }
+        };
 
         fn format_types(types: &[String], join: &str) -> String {
             types
                 .iter()
-                .map(|t| format!("<div data-code=\"type\">{}</div>", t))
+                .map(|t| format!("<div data-code=\"type\">{}</div>", escape_html(t)))
                 .collect::<Vec<String>>()
                 .join(join)
         }
@@ -569,7 +1410,7 @@ impl TidyDocs {
         if let Some(description) = &self.description {
             result.push_str(&format!(
                 "<div data-code=\"text\">{}</div>",
-                description.replace("\n", "")
+                format_text(description)
             ));
         }
 
@@ -584,7 +1425,7 @@ impl TidyDocs {
                 };
                 arg_str.push_str(&format!(
                     "<div data-code=\"function-arg\">{}{}{}{}</div>",
-                    arg.name,
+                    escape_html(&arg.name),
                     if arg.default.is_some() { "?" } else { "" },
                     types,
                     if i < self.arguments.len() - 1 {
@@ -602,7 +1443,7 @@ impl TidyDocs {
 
             result.push_str(&format!(
                 "<div data-code=\"function\"><div data-code=\"name\">{}</div>({}){}</div>",
-                self.name,
+                escape_html(&self.name),
                 arg_str.trim_end_matches(", "),
                 return_types
             ));
@@ -611,15 +1452,15 @@ impl TidyDocs {
                 result.push_str("<div data-code=\"arg\">");
                 result.push_str(&format!(
                     "<div data-code=\"arg-heading\"><div data-code=\"arg-name\">{}</div> {}</div>",
-                    arg.name,
+                    escape_html(&arg.name),
                     format_types(&arg.types, " <div data-code=\"or\">or</div> ")
                 ));
                 if let Some(description) = &arg.description {
                     result.push_str(&format!(
                         "<div data-code=\"arg-content\">{}</div><div data-code=\"arg-default\">{}</div>",
-                        description,
+                        format_text(description),
                         if arg.default.is_some() {
-                            format!("Default: {}", arg.default.as_ref().unwrap())
+                            format!("Default: {}", escape_html(arg.default.as_ref().unwrap()))
                         } else {
                             "".to_string()
                         }
@@ -635,7 +1476,7 @@ impl TidyDocs {
             };
             result.push_str(&format!(
                 "<div data-code=\"function\"><div data-code=\"name\">{}</div>{}{}</div>",
-                self.name,
+                escape_html(&self.name),
                 match self.type_ {
                     TidyType::Function => "()",
                     TidyType::Variable => "",
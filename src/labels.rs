@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+
+use typst::{
+    foundations::{Content, Value},
+    model::Document,
+    syntax::{FileId, LinkedNode, Side, Source, SyntaxKind},
+};
+
+use crate::{
+    file_entry::FileEntry,
+    js_types::{Completion, CompletionDetail, CompletionKind, LabelInfo, ResolvedSpan},
+};
+
+/// Longest label description, in characters.
+const MAX_DESCRIPTION_LEN: usize = 60;
+
+/// `text` on a single line, truncated to `MAX_DESCRIPTION_LEN`. `None` if it is blank.
+fn short_description(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        None
+    } else if text.chars().count() > MAX_DESCRIPTION_LEN {
+        let cut = text
+            .chars()
+            .take(MAX_DESCRIPTION_LEN - 1)
+            .collect::<String>();
+        Some(format!("{}…", cut.trim_end()))
+    } else {
+        Some(text)
+    }
+}
+
+/// The text of the heading a label is written in, without the `=` markers and the label.
+fn heading_text(node: &LinkedNode) -> Option<String> {
+    let mut parent = node.parent();
+    while let Some(ancestor) = parent {
+        if ancestor.kind() == SyntaxKind::Heading {
+            let text = ancestor
+                .children()
+                .filter(|child| child.kind() == SyntaxKind::Markup)
+                .map(|markup| markup.get().clone().into_text())
+                .collect::<Vec<_>>()
+                .concat()
+                .replace(node.text().as_str(), "");
+            return short_description(&text);
+        }
+        parent = ancestor.parent();
+    }
+    None
+}
+
+/// The caption of a figure, the body of a heading or otherwise the element's name.
+fn element_description(elem: &Content) -> Option<String> {
+    let text = |value: Value| match value {
+        Value::Content(content) => Some(content.plain_text()),
+        Value::Str(text) => Some(text.as_str().into()),
+        _ => None,
+    };
+
+    if let Ok(Value::Content(caption)) = elem.get_by_name("caption") {
+        let body = caption.get_by_name("body").ok().and_then(text);
+        if let Some(description) = body.as_deref().and_then(short_description) {
+            return Some(description);
+        }
+    }
+    if let Some(description) = elem
+        .get_by_name("body")
+        .ok()
+        .and_then(text)
+        .as_deref()
+        .and_then(short_description)
+    {
+        return Some(description);
+    }
+    Some(elem.func().name().to_string())
+}
+
+fn mark_duplicates(labels: &mut [LabelInfo]) {
+    let mut counts = HashMap::new();
+    for label in labels.iter() {
+        *counts.entry(label.name.clone()).or_insert(0) += 1;
+    }
+    for label in labels.iter_mut() {
+        label.duplicate = counts[&label.name] > 1;
+    }
+}
+
+fn sort(labels: &mut [LabelInfo]) {
+    labels.sort_by(|a, b| {
+        a.file_path
+            .cmp(&b.file_path)
+            .then(a.span.start_offset.cmp(&b.span.start_offset))
+    });
+}
+
+fn collect(node: &LinkedNode, source: &Source, labels: &mut Vec<LabelInfo>) {
+    if node.kind() == SyntaxKind::Label {
+        let name = node.text().trim_start_matches('<').trim_end_matches('>');
+        labels.push(LabelInfo {
+            name: name.to_string(),
+            file_path: source
+                .id()
+                .vpath()
+                .as_rooted_path()
+                .to_string_lossy()
+                .into_owned(),
+            span: ResolvedSpan::from_source(node.span(), source),
+            description: heading_text(node),
+            duplicate: false,
+        });
+    }
+
+    for child in node.children() {
+        collect(&child, source, labels);
+    }
+}
+
+/// Labels declared in the syntax of all project sources, sorted by file and offset.
+/// Names declared more than once are marked as duplicates.
+pub fn source_labels(sources: &HashMap<FileId, FileEntry>) -> Vec<LabelInfo> {
+    let mut labels = Vec::new();
+    for (id, entry) in sources.iter() {
+        if id.package().is_none() {
+            collect(
+                &LinkedNode::new(entry.source.root()),
+                &entry.source,
+                &mut labels,
+            );
+        }
+    }
+
+    mark_duplicates(&mut labels);
+    sort(&mut labels);
+    labels
+}
+
+/// Labels attached to elements of a compiled document, including those added by show rules
+/// or loops, sorted like `source_labels`. Elements from packages are left out.
+pub fn document_labels(doc: &Document, sources: &HashMap<FileId, FileEntry>) -> Vec<LabelInfo> {
+    let mut labels = doc
+        .introspector
+        .all()
+        .filter_map(|elem| {
+            let label = elem.label()?;
+            let span = elem.span();
+            if span.id().is_some_and(|id| id.package().is_some()) {
+                return None;
+            }
+            let span = ResolvedSpan::from_sources(span, sources);
+            Some(LabelInfo {
+                name: label.as_str().to_string(),
+                file_path: span.file_path.clone(),
+                span,
+                description: element_description(elem),
+                duplicate: false,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    mark_duplicates(&mut labels);
+    sort(&mut labels);
+    labels
+}
+
+/// Where the label name starts if the cursor is inside a reference like `@fig-`.
+pub fn ref_start(source: &Source, offset: usize) -> Option<usize> {
+    let leaf = LinkedNode::new(source.root()).leaf_at(offset, Side::Before)?;
+    match leaf.kind() {
+        SyntaxKind::RefMarker | SyntaxKind::Ref => Some(leaf.offset() + 1),
+        _ if leaf.parent_kind() == Some(SyntaxKind::Ref) => Some(leaf.parent()?.offset() + 1),
+        _ => None,
+    }
+}
+
+/// Completions for every distinct label name.
+pub fn label_completions(labels: &[LabelInfo]) -> Vec<Completion> {
+    let mut seen = HashSet::new();
+    labels
+        .iter()
+        .filter(|label| seen.insert(label.name.clone()))
+        .map(|label| Completion {
+            kind: CompletionDetail {
+                kind: CompletionKind::Constant,
+                detail: None,
+            },
+            label: label.name.clone(),
+            apply: None,
+            detail: Some(
+                label
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| label.file_path.clone()),
+            ),
+        })
+        .collect()
+}
@@ -1,23 +1,28 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     io::Read,
     path::PathBuf,
     str::FromStr,
-    sync::{Arc, Mutex, OnceLock, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
 };
 
 use ast::{get_args, get_prev, get_prev_kind};
-use chrono::{format, DateTime, Datelike, Local};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate};
 use file_entry::FileEntry;
 use flate2::read::GzDecoder;
 use js_types::RawPackageSpec;
+use lockfile::{LockedPackage, Lockfile};
+use logging::LogLevel;
+use parking_lot::{Mutex, RwLock};
 use tar::Archive;
-use tidy::parse_doc_str;
-// use parking_lot::RwLock;
+use tidy::{builtin_docs, collect_tidy_doc, parse_doc_str};
 use typst::{
-    diag::{EcoString, FileResult},
-    foundations::{Bytes, Datetime},
-    layout::Abs,
+    diag::{eco_format, EcoString, FileError, FileResult, PackageError, PackageResult},
+    foundations::{Bytes, Datetime, Dict, Smart, Value},
+    layout::{Abs, PageElem},
     model::Document,
     syntax::{
         package::{PackageSpec, PackageVersion},
@@ -27,34 +32,135 @@ use typst::{
     utils::LazyHash,
     Library, World,
 };
-use typst_ide::{analyze_import, tooltip};
+use typst_ide::tooltip;
+use typstyle_core::{Config, Typstyle};
 use wasm_bindgen::prelude::*;
 
+mod accessibility;
+mod annotate;
 mod ast;
+mod bibliography;
+mod cancel;
+mod diff;
+mod encoding;
+mod exports;
 mod fetch;
 mod file_entry;
+mod folding;
+mod fonts;
+mod frames;
+mod fuzzy;
+mod graph;
+mod highlight;
 mod js_types;
+mod labels;
+mod limits;
+mod lockfile;
+mod logging;
+mod package_index;
+mod paths;
+mod prefetch;
+mod preview;
+mod profile;
+mod progress;
+mod prose;
+mod query;
+mod quickfix;
+mod references;
+mod registry;
+mod state;
+mod stats;
+mod symbols;
 mod tidy;
 
+/// Documents kept by `compile_file` for IDE features, the least recently compiled is dropped.
+const MAX_ENTRY_DOCS: usize = 3;
+
+/// Upper bound for PNG rendering, an A4 page at 10 ppp is already ~6000x8400 pixels.
+const MAX_PIXELS_PER_PT: f32 = 10.0;
+
+/// Default line width used by `format`.
+const FORMAT_WIDTH: usize = 120;
+
 #[wasm_bindgen]
 pub struct SuiteCore {
     library: OnceLock<LazyHash<Library>>,
 
+    inputs: BTreeMap<String, String>, // `sys.inputs` of the library
+
+    sized_library: OnceLock<LazyHash<Library>>, // `library` with the page size of `compile_with_page`
+
+    page_size: Option<(f64, f64)>, // In pt, what `sized_library` was built for
+
+    sized_compile: bool, // Whether the running compile uses `sized_library`
+
     book: OnceLock<LazyHash<FontBook>>,
 
     sources: Arc<RwLock<HashMap<FileId, FileEntry>>>,
 
-    fonts: Mutex<Vec<Font>>,
+    fonts: RwLock<Vec<Font>>, // Read by every `font()` call, reads never block each other
 
     root: PathBuf,
 
-    now: OnceLock<DateTime<Local>>,
+    now: OnceLock<DateTime<FixedOffset>>, // Evaluated once per compile
+
+    clock: Option<DateTime<FixedOffset>>, // Host provided "now", falls back to the system clock
 
     last_doc: Mutex<Option<Document>>,
 
-    packages: RwLock<Vec<PackageWrapper>>,
+    page_hashes: Vec<u128>, // Of the pages rendered by the last compile
+
+    packages: Arc<RwLock<Vec<PackageWrapper>>>,
 
     package_index: OnceLock<Vec<(PackageSpec, Option<EcoString>)>>,
+
+    progress: Option<progress::ProgressCallback>,
+
+    cancellation: Option<cancel::CancellationToken>,
+
+    compiling: bool, // Whether file loads should observe the cancellation token
+
+    dependencies: Mutex<HashSet<FileId>>, // Files accessed by the last compile
+
+    entrypoint: Option<FileId>, // Overrides the main file derived from root for a single compile
+
+    scratch_prefix: String,
+
+    scratch_counter: usize,
+
+    limits: js_types::ResourceLimits,
+
+    latest_versions: Arc<RwLock<HashMap<(EcoString, EcoString), PackageVersion>>>, // Newest versions from the package index
+
+    index_downloaded: Arc<AtomicBool>, // Whether the preview index was downloaded (or tried to)
+
+    registries: Vec<registry::Registry>, // Custom registries, @preview is built in
+
+    profiling: Option<profile::FetchPhases>, // Set while profiling is on
+
+    last_profile: Option<js_types::ProfileReport>,
+
+    doc_options: js_types::DocOptions, // How hover documentation is rendered
+
+    preview: preview::PreviewStyle, // Applied to rendered pages only, never to `last_doc`
+
+    strict: bool, // Warnings fail the compile
+
+    target: js_types::Target, // Selects the export method, kept by `reset`
+
+    auto_register_packages: bool, // Unknown packages met while compiling are registered
+
+    package_index_stale: Arc<AtomicBool>, // Set when packages were registered or pinned behind `&self`
+
+    library_symbols: Mutex<HashMap<String, Vec<js_types::LibrarySymbol>>>, // By module, "" is the global scope
+
+    entry_docs: Mutex<Vec<(FileId, Document)>>, // By entrypoint, most recent first
+
+    doc_entrypoints: Mutex<HashMap<FileId, FileId>>, // The entrypoint each file was last compiled under
+
+    missing_fonts: Vec<String>, // Families the last compile warned about
+
+    deadline: Option<(f64, usize)>, // Start and `max_compile_ms` of the running compile
 }
 
 #[derive(Clone, Debug)]
@@ -85,6 +191,15 @@ impl ExtendedPackageVersion {
     }
 }
 
+impl std::fmt::Display for ExtendedPackageVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Latest => write!(f, "latest"),
+            Self::Version(v) => write!(f, "{}", v),
+        }
+    }
+}
+
 impl From<PackageVersion> for ExtendedPackageVersion {
     fn from(version: PackageVersion) -> Self {
         Self::Version(version)
@@ -109,6 +224,10 @@ impl From<RawPackageSpec> for PackageWrapper {
             version: ExtendedPackageVersion::from_str(spec.version.as_str()).unwrap(),
             fetched: false,
             description: spec.description.map(EcoString::from),
+            resolved: None,
+            hash: None,
+            locked_hash: None,
+            error: None,
         }
     }
 }
@@ -120,6 +239,10 @@ struct PackageWrapper {
     version: ExtendedPackageVersion,
     fetched: bool,
     description: Option<EcoString>,
+    resolved: Option<PackageVersion>, // The version that actually got fetched
+    hash: Option<String>,             // Content hash of the fetched files
+    locked_hash: Option<String>,      // Expected content hash from an applied lockfile
+    error: Option<PackageError>,      // Why the last download failed
 }
 
 impl PackageWrapper {
@@ -135,6 +258,52 @@ impl PackageWrapper {
             self.fetched
         )
     }
+
+    fn to_locked(&self) -> Option<LockedPackage> {
+        let resolved = match (&self.resolved, &self.version) {
+            (Some(resolved), _) => *resolved,
+            (None, ExtendedPackageVersion::Version(v)) => *v,
+            (None, ExtendedPackageVersion::Latest) => return None,
+        };
+
+        Some(LockedPackage {
+            namespace: self.namespace.to_string(),
+            name: self.name.to_string(),
+            version: self.version.to_string(),
+            resolved: resolved.to_string(),
+            hash: self.hash.clone(),
+        })
+    }
+
+    fn to_state(&self) -> state::StatePackage {
+        state::StatePackage {
+            namespace: self.namespace.to_string(),
+            name: self.name.to_string(),
+            version: self.version.to_string(),
+            fetched: self.fetched,
+            description: self.description.as_ref().map(|d| d.to_string()),
+            resolved: self.resolved.map(|v| v.to_string()),
+            hash: self.hash.clone(),
+            locked_hash: self.locked_hash.clone(),
+        }
+    }
+
+    fn from_state(package: state::StatePackage) -> Result<Self, String> {
+        Ok(Self {
+            namespace: package.namespace.into(),
+            name: package.name.into(),
+            version: ExtendedPackageVersion::from_str(&package.version)?,
+            fetched: package.fetched,
+            description: package.description.map(EcoString::from),
+            resolved: package
+                .resolved
+                .map(|v| PackageVersion::from_str(&v))
+                .transpose()?,
+            hash: package.hash,
+            locked_hash: package.locked_hash,
+            error: None,
+        })
+    }
 }
 
 impl From<PackageSpec> for PackageWrapper {
@@ -145,6 +314,10 @@ impl From<PackageSpec> for PackageWrapper {
             version: ExtendedPackageVersion::from(spec.version),
             fetched: false,
             description: None,
+            resolved: None,
+            hash: None,
+            locked_hash: None,
+            error: None,
         }
     }
 }
@@ -160,66 +333,167 @@ impl From<PackageWrapper> for PackageSpec {
 }
 
 trait TPFetchable {
-    fn fetch(&self) -> HashMap<FileId, FileEntry>;
+    /// Unpacks a downloaded archive, keying the files by `self`.
+    fn extract(
+        &self,
+        data: Vec<u8>,
+        limits: &js_types::ResourceLimits,
+    ) -> PackageResult<HashMap<FileId, FileEntry>>;
 }
 
 impl TPFetchable for PackageSpec {
-    fn fetch(&self) -> HashMap<FileId, FileEntry> {
-        let path = {
-            if self.namespace().starts_with("wolframe-") {
-                let args = self.namespace().split("-").collect::<Vec<&str>>();
-                format!(
-                    "/packages/download?uname={}&pname={}",
-                    args[1..].join("-"),
-                    self.name()
-                )
-            } else {
-                format!(
-                    "https://packages.typst.org/preview/{}-{}.tar.gz",
-                    self.name, self.version
-                )
-            }
-        };
-        log(format!(
-            "fetching package: {}, {:?}, {}, {}",
-            path,
-            self,
-            self.namespace().starts_with("@wolframe-"),
-            self.namespace()
-        )
-        .as_str());
-        let fetch = xml_get_sync(path);
-        let cursor = std::io::Cursor::new(fetch);
+    fn extract(
+        &self,
+        data: Vec<u8>,
+        limits: &js_types::ResourceLimits,
+    ) -> PackageResult<HashMap<FileId, FileEntry>> {
+        if data.is_empty() {
+            return Err(PackageError::NetworkFailed(Some(eco_format!(
+                "package {} could not be downloaded (empty response)",
+                self
+            ))));
+        }
+        let cursor = std::io::Cursor::new(data);
         let gz_decoder = GzDecoder::new(cursor);
         let mut archive = Archive::new(gz_decoder);
 
-        archive
-            .entries()
-            .unwrap()
-            .filter(|entry| {
-                entry.is_ok()
-                    && entry.as_ref().unwrap().header().entry_type() != tar::EntryType::Directory
-            })
-            .map(|entry| {
-                let mut entry = entry.unwrap();
-                let path = entry.path().unwrap().to_string_lossy().into_owned();
-                let id = FileId::new(Some(self.clone()), VirtualPath::new(path.clone()));
+        let limit_error =
+            |e: limits::LimitExceeded| PackageError::Other(Some(eco_format!("{}", e)));
+        let malformed = |e: std::io::Error| {
+            PackageError::MalformedArchive(Some(eco_format!("package {}: {}", self, e)))
+        };
+        let mut sources = HashMap::new();
+        let mut links = Vec::new();
+        let mut total_size = 0;
+
+        // Limits are checked while extracting so oversized archives are never fully decompressed
+        for entry in archive.entries().map_err(malformed)? {
+            let entry = entry.map_err(malformed)?;
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_symlink() || entry_type.is_hard_link() {
+                let path = entry.path().map_err(malformed)?;
+                let Some(target) = entry.link_name().map_err(malformed)? else {
+                    continue;
+                };
+                let target = target.to_string_lossy();
+                // Symlinks are relative to their directory, absolute ones leave the package
+                let target = if !entry_type.is_symlink() {
+                    paths::normalize(&target)
+                } else if target.starts_with('/') {
+                    continue;
+                } else {
+                    paths::normalize(&path.with_file_name(&*target).to_string_lossy())
+                };
+                links.push((paths::normalize(&path.to_string_lossy()), target));
+                continue;
+            }
+            if !entry_type.is_file() {
+                continue;
+            }
+            limits::check(
+                "max_archive_entries",
+                limits.max_archive_entries,
+                sources.len() + 1,
+                self,
+            )
+            .map_err(limit_error)?;
+
+            // `./src/lib.typ` must get the same id typst computes for `src/lib.typ`
+            let path = entry.path().map_err(malformed)?;
+            let id = FileId::new(
+                Some(self.clone()),
+                paths::normalize(&path.to_string_lossy()),
+            );
+
+            let remaining = limits
+                .max_archive_size
+                .map_or(u64::MAX, |max| max.saturating_sub(total_size) as u64 + 1)
+                .min(limits.max_file_size.map_or(u64::MAX, |max| max as u64 + 1));
+            let mut content = Vec::new();
+            entry
+                .take(remaining)
+                .read_to_end(&mut content)
+                .map_err(malformed)?;
+            limits::check(
+                "max_file_size",
+                limits.max_file_size,
+                content.len(),
+                paths::qualified_path(id).display(),
+            )
+            .map_err(limit_error)?;
+            total_size += content.len();
+            limits::check(
+                "max_archive_size",
+                limits.max_archive_size,
+                total_size,
+                self,
+            )
+            .map_err(limit_error)?;
+
+            sources.insert(id, FileEntry::from_bytes(id, content));
+        }
 
-                // log(format!("extracting: {}, id: {:?}", path, id).as_str()); debug
+        // Links become copies of their target, links to links resolve over several passes
+        while !links.is_empty() {
+            let pending = links.len();
+            let mut unresolved = Vec::new();
+            for (path, target) in links {
+                let target = FileId::new(Some(self.clone()), target);
+                let Some(bytes) = sources.get(&target).map(FileEntry::bytes) else {
+                    unresolved.push((path, target.vpath().clone()));
+                    continue;
+                };
+                limits::check(
+                    "max_archive_entries",
+                    limits.max_archive_entries,
+                    sources.len() + 1,
+                    self,
+                )
+                .map_err(limit_error)?;
+                total_size += bytes.len();
+                limits::check(
+                    "max_archive_size",
+                    limits.max_archive_size,
+                    total_size,
+                    self,
+                )
+                .map_err(limit_error)?;
 
-                let mut content = Vec::new();
-                entry.read_to_end(&mut content).unwrap();
+                let id = FileId::new(Some(self.clone()), path);
+                sources.insert(id, FileEntry::from_bytes(id, bytes.to_vec()));
+            }
+            // Dangling or cyclic links are skipped
+            if unresolved.len() == pending {
+                break;
+            }
+            links = unresolved;
+        }
 
-                (id, FileEntry::new(id, String::from_utf8(content).unwrap()))
-            })
-            .collect::<HashMap<FileId, FileEntry>>()
+        Ok(sources)
     }
 }
 
+/// Message of a thrown JS value, which may be a string or an `Error`.
+fn js_error_message(error: &JsValue) -> String {
+    error
+        .as_string()
+        .or_else(|| {
+            error
+                .dyn_ref::<js_sys::Error>()
+                .map(|e| String::from(e.message()))
+        })
+        .unwrap_or_else(|| format!("{:?}", error))
+}
+
 trait UnifiedPackageSpec {
     fn namespace(&self) -> &EcoString;
     fn name(&self) -> &EcoString;
     fn version(&self) -> &PackageVersion;
+
+    /// Whether this stands for whatever version gets imported.
+    fn is_latest(&self) -> bool {
+        false
+    }
 }
 
 impl UnifiedPackageSpec for PackageSpec {
@@ -248,6 +522,10 @@ impl UnifiedPackageSpec for PackageWrapper {
     fn version(&self) -> &PackageVersion {
         self.version.version()
     }
+
+    fn is_latest(&self) -> bool {
+        self.version == ExtendedPackageVersion::Latest
+    }
 }
 
 trait TPComparable {
@@ -266,14 +544,12 @@ where
     {
         self.namespace() == other.namespace()
             && self.name() == other.name()
-            && self.version() == other.version()
+            && (self.version() == other.version() || self.is_latest() || other.is_latest())
     }
 }
 
 #[wasm_bindgen]
 extern "C" {
-    pub fn xml_get_sync(path: String) -> Vec<u8>;
-
     pub fn logWasm(s: &str);
 
     #[wasm_bindgen(js_name = logWasm)]
@@ -281,11 +557,17 @@ extern "C" {
 
     pub fn errorWasm(s: &str);
 
+    /// Structured events like `compile_done`, see `logging::emit`.
+    pub fn emitEvent(kind: &str, payload: JsValue);
+
     // Use `js_namespace` here to bind `console.log(..)` instead of just
     // `log(..)`
     #[wasm_bindgen(js_namespace = console)]
     fn log(s: &str);
 
+    #[wasm_bindgen(js_namespace = console)]
+    fn warn(s: &str);
+
     // The `console.log` is quite polymorphic, so we can bind it with multiple
     // signatures. Note that we need to use `js_name` to ensure we always call
     // `log` in JS.
@@ -302,36 +584,281 @@ impl SuiteCore {
     #[wasm_bindgen(constructor)]
     pub fn new(root: String) -> Self {
         console_error_panic_hook::set_once();
-        let (book, fonts) = Self::start_embedded_fonts();
-
-        let book_lock = OnceLock::new();
-        let _ = book_lock.set(LazyHash::new(book)); // TODO: add proper error handling
+        let fonts = Self::start_embedded_fonts();
+        let book = OnceLock::from(LazyHash::new(FontBook::from_fonts(&fonts)));
 
         Self {
             library: OnceLock::default(),
-            book: book_lock,
+            sized_library: OnceLock::default(),
+            page_size: None,
+            sized_compile: false,
+            inputs: BTreeMap::new(),
+            book,
             sources: Arc::new(RwLock::new(HashMap::new())),
-            fonts: Mutex::new(fonts),
+            fonts: RwLock::new(fonts),
             now: OnceLock::default(),
+            clock: None,
             root: PathBuf::from(root),
             last_doc: Mutex::new(None),
-            packages: RwLock::new(Vec::new()),
+            page_hashes: Vec::new(),
+            packages: Arc::new(RwLock::new(Vec::new())),
             package_index: OnceLock::default(),
+            progress: None,
+            cancellation: None,
+            compiling: false,
+            dependencies: Mutex::new(HashSet::new()),
+            entrypoint: None,
+            scratch_prefix: "/__scratch__".to_string(),
+            scratch_counter: 0,
+            limits: js_types::ResourceLimits::default(),
+            latest_versions: Arc::new(RwLock::new(HashMap::new())),
+            index_downloaded: Arc::new(AtomicBool::new(false)),
+            registries: Vec::new(),
+            profiling: None,
+            last_profile: None,
+            doc_options: js_types::DocOptions::default(),
+            preview: preview::PreviewStyle::default(),
+            strict: false,
+            target: js_types::Target::Paged,
+            auto_register_packages: false,
+            package_index_stale: Arc::new(AtomicBool::new(false)),
+            library_symbols: Mutex::new(HashMap::new()),
+            entry_docs: Mutex::new(Vec::new()),
+            doc_entrypoints: Mutex::new(HashMap::new()),
+            missing_fonts: Vec::new(),
+            deadline: None,
         }
     }
 
     pub fn get_files(&self) -> Vec<String> {
         self.sources
             .read()
-            .unwrap()
             .keys()
+            .filter(|id| !self.is_scratch(**id))
             .map(|id| id.vpath().as_rootless_path().to_str().unwrap().to_string())
             .collect()
     }
 
+    /// Immediate children of a directory, directories first. Package files are only listed
+    /// with `include_packages`, under `/@namespace/name:version/`.
+    pub fn list_dir(
+        &self,
+        path: String,
+        include_packages: Option<bool>,
+    ) -> Vec<js_types::DirEntry> {
+        let include_packages = include_packages.unwrap_or(false);
+        let dir = paths::normalize(&path);
+        let dir = dir.as_rooted_path();
+
+        let mut entries: Vec<js_types::DirEntry> = Vec::new();
+        for id in self.sources.read().keys() {
+            let is_package = id.package().is_some();
+            if (is_package && !include_packages) || self.is_scratch(*id) {
+                continue;
+            }
+            let path = paths::qualified_path(*id);
+            let Ok(rest) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let mut components = rest.components();
+            let Some(name) = components.next() else {
+                continue;
+            };
+            let name = name.as_os_str().to_string_lossy().into_owned();
+            let is_dir = components.next().is_some();
+
+            match entries.iter_mut().find(|entry| entry.name == name) {
+                Some(entry) => entry.is_dir |= is_dir,
+                None => entries.push(js_types::DirEntry {
+                    path: dir.join(&name).to_string_lossy().into_owned(),
+                    name,
+                    is_dir,
+                    is_package,
+                }),
+            }
+        }
+
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+        entries
+    }
+
+    /// Whether a file exists, package files are addressed as `/@namespace/name:version/path`.
+    pub fn file_exists(&self, path: String) -> bool {
+        let id = paths::parse_qualified_path(&path);
+        !self.is_scratch(id) && self.sources.read().contains_key(&id)
+    }
+
+    /// Brings the instance back into a consistent state after a call panicked: drops the
+    /// last document and the state of an interrupted compile and repairs files stored under
+    /// the wrong id. Returns whether all internal locks are free again, the instance is
+    /// usable then. The locks never poison, so a panic cannot leave them unusable for good.
+    pub fn recover(&mut self) -> bool {
+        if let Some(mut last_doc) = self.last_doc.try_lock() {
+            *last_doc = None;
+        }
+        self.page_hashes.clear();
+        self.compiling = false;
+        self.sized_compile = false;
+        self.entrypoint = None;
+        self.now = OnceLock::default();
+        if let Some(mut dependencies) = self.dependencies.try_lock() {
+            dependencies.clear();
+        }
+        if let Some(mut entry_docs) = self.entry_docs.try_lock() {
+            entry_docs.clear();
+        }
+        if let Some(mut doc_entrypoints) = self.doc_entrypoints.try_lock() {
+            doc_entrypoints.clear();
+        }
+
+        if let Some(mut sources) = self.sources.try_write() {
+            for (id, entry) in sources.iter_mut() {
+                if entry.source.id() != *id {
+                    logging::log(LogLevel::Warn, || {
+                        format!("recover: repairing the id of {:?}", id)
+                    });
+                    *entry = FileEntry::from_bytes(*id, entry.bytes().to_vec());
+                }
+            }
+        }
+
+        self.sources.try_write().is_some()
+            && self.packages.try_write().is_some()
+            && self.latest_versions.try_write().is_some()
+            && self.last_doc.try_lock().is_some()
+            && self.dependencies.try_lock().is_some()
+            && self.entry_docs.try_lock().is_some()
+            && self.doc_entrypoints.try_lock().is_some()
+            && self.fonts.try_write().is_some()
+    }
+
+    /// Drops all files, packages and the last document to switch projects, keeping the
+    /// parsed fonts and the standard library which make `new` slow.
+    pub fn clear_workspace(&mut self) {
+        // Fresh maps, so a running `prefetch_packages` cannot fill the new workspace
+        self.sources = Arc::new(RwLock::new(HashMap::new()));
+        self.packages = Arc::new(RwLock::new(Vec::new()));
+        self.package_index = OnceLock::default();
+        *self.last_doc.lock() = None;
+        self.entry_docs.lock().clear();
+        self.doc_entrypoints.lock().clear();
+        self.missing_fonts.clear();
+        self.page_hashes.clear();
+        self.entrypoint = None;
+        self.scratch_counter = 0;
+        self.now = OnceLock::default();
+    }
+
+    /// Snapshots the project (files, packages, registries and inputs) as MessagePack bytes.
+    /// Fonts and the standard library are not included, `import_state` on a fresh instance
+    /// restores the rest.
+    pub fn export_state(&self) -> Result<Vec<u8>, JsValue> {
+        let files = self
+            .sources
+            .read()
+            .iter()
+            .filter(|(id, _)| !self.is_scratch(**id))
+            .map(|(id, entry)| state::StateFile {
+                path: paths::qualified_path(*id).to_string_lossy().into_owned(),
+                data: entry.bytes().to_vec(),
+            })
+            .collect();
+        let packages = self
+            .packages
+            .read()
+            .iter()
+            .map(PackageWrapper::to_state)
+            .collect();
+        let latest_versions = self
+            .latest_versions
+            .read()
+            .iter()
+            .map(|((namespace, name), version)| state::StateLatest {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                version: version.to_string(),
+            })
+            .collect();
+        let registries = self
+            .registries
+            .iter()
+            .map(|registry| state::StateRegistry {
+                prefix: registry.prefix.clone(),
+                url_template: registry.url_template.clone(),
+                refetch_latest: registry.refetch_latest,
+            })
+            .collect();
+
+        state::ProjectState {
+            version: state::STATE_VERSION,
+            root: self.root.to_string_lossy().into_owned(),
+            files,
+            packages,
+            latest_versions,
+            registries,
+            inputs: self.inputs.clone(),
+        }
+        .to_bytes()
+        .map_err(|e| JsValue::from_str(&format!("InvalidState: {}", e)))
+    }
+
+    /// Replaces the workspace with a snapshot from `export_state`. Its files are checked
+    /// against the resource limits first, a snapshot exceeding one is rejected as a whole with
+    /// a `LimitExceeded` error and the workspace is left unchanged.
+    pub fn import_state(&mut self, data: Vec<u8>) -> Result<(), JsValue> {
+        let invalid = |e: String| JsValue::from_str(&format!("InvalidState: {}", e));
+        let state = state::ProjectState::from_bytes(&data).map_err(invalid)?;
+
+        // Validate everything before the current workspace is dropped
+        let packages = state
+            .packages
+            .into_iter()
+            .map(PackageWrapper::from_state)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        let latest_versions = state
+            .latest_versions
+            .into_iter()
+            .map(|latest| {
+                let version = PackageVersion::from_str(&latest.version)
+                    .map_err(|e| invalid(e.to_string()))?;
+                Ok(((latest.namespace.into(), latest.name.into()), version))
+            })
+            .collect::<Result<HashMap<_, _>, JsValue>>()?;
+        self.check_state_limits(&state.files)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        self.clear_workspace();
+        self.root = PathBuf::from(state.root);
+        {
+            let mut sources = self.sources.write();
+            for file in state.files {
+                let id = paths::parse_qualified_path(&file.path);
+                sources.insert(id, FileEntry::from_bytes(id, file.data));
+            }
+        }
+        *self.packages.write() = packages;
+        *self.latest_versions.write() = latest_versions;
+        self.registries = state
+            .registries
+            .into_iter()
+            .map(|registry| registry::Registry {
+                prefix: registry.prefix,
+                url_template: registry.url_template,
+                refetch_latest: registry.refetch_latest,
+            })
+            .collect();
+        if self.inputs != state.inputs {
+            self.inputs = state.inputs;
+            self.reset_library();
+        }
+
+        Ok(())
+    }
+
     pub fn delete_file(&mut self, file: String) -> Result<(), JsValue> {
         let id = FileId::new(None, VirtualPath::new(&file));
-        self.sources.write().unwrap().remove(&id);
+        self.sources.write().remove(&id);
 
         Ok(())
     }
@@ -341,7 +868,6 @@ impl SuiteCore {
         if !self
             .sources
             .read()
-            .unwrap()
             .keys()
             .any(|id| id.vpath().as_rooted_path().to_str().unwrap() == root.as_str())
         {
@@ -353,75 +879,672 @@ impl SuiteCore {
     }
 
     pub fn add_packages(&mut self, packages: Vec<RawPackageSpec>) {
-        let mut lock = self.packages.write().unwrap();
+        let mut lock = self.packages.write();
         for package in packages {
             lock.push(package.into());
         }
+        self.package_index = OnceLock::default();
+    }
+
+    /// Registers a package whose files are provided directly, so it is never downloaded.
+    /// `files` is an array of `[path, content]` pairs with paths relative to the package root.
+    pub fn add_local_package(
+        &mut self,
+        spec: RawPackageSpec,
+        files: JsValue,
+    ) -> Result<(), JsValue> {
+        let files: Vec<(String, String)> = serde_wasm_bindgen::from_value(files)
+            .map_err(|e| JsValue::from_str(&format!("invalid package files: {}", e)))?;
+        let mut wrapper: PackageWrapper = spec.into();
+        let ExtendedPackageVersion::Version(version) = wrapper.version else {
+            return Err(JsValue::from_str(
+                "local packages need a concrete version, not latest",
+            ));
+        };
+        let package = PackageSpec {
+            namespace: wrapper.namespace.clone(),
+            name: wrapper.name.clone(),
+            version,
+        };
+
+        let to_error = |e: limits::LimitExceeded| JsValue::from_str(&e.to_string());
+        limits::check(
+            "max_archive_entries",
+            self.limits.max_archive_entries,
+            files.len(),
+            &package,
+        )
+        .map_err(to_error)?;
+        for (path, content) in &files {
+            limits::check(
+                "max_file_size",
+                self.limits.max_file_size,
+                content.len(),
+                format!("{}/{}", package, path),
+            )
+            .map_err(to_error)?;
+        }
+        let total_size = files.iter().map(|(_, content)| content.len()).sum();
+        limits::check(
+            "max_archive_size",
+            self.limits.max_archive_size,
+            total_size,
+            &package,
+        )
+        .map_err(to_error)?;
+
+        let entries = files
+            .into_iter()
+            .map(|(path, content)| {
+                let id = FileId::new(Some(package.clone()), VirtualPath::new(path));
+                (id, FileEntry::new(id, content))
+            })
+            .collect::<HashMap<FileId, FileEntry>>();
+
+        wrapper.fetched = true;
+        wrapper.resolved = Some(version);
+        wrapper.hash = Some(lockfile::content_hash(&entries));
+
+        let mut sources = self.sources.write();
+        sources.retain(|id, _| id.package() != Some(&package));
+        sources.extend(entries);
+
+        let mut packages = self.packages.write();
+        packages.retain(|p| {
+            !(p.namespace == package.namespace
+                && p.name == package.name
+                && p.version == wrapper.version)
+        });
+        packages.push(wrapper);
+        self.package_index = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Unregisters a package and drops its fetched files.
+    pub fn remove_package(
+        &mut self,
+        namespace: String,
+        name: String,
+        version: String,
+    ) -> Result<(), JsValue> {
+        let version =
+            ExtendedPackageVersion::from_str(&version).map_err(|e| JsValue::from_str(&e))?;
+
+        let mut lock = self.packages.write();
+        let index = lock
+            .iter()
+            .position(|p| {
+                p.namespace == namespace.as_str() && p.name == name.as_str() && p.version == version
+            })
+            .ok_or_else(|| {
+                JsValue::from_str(&format!(
+                    "NotFound: package @{}/{}:{}",
+                    namespace, name, version
+                ))
+            })?;
+        let removed = lock.remove(index);
+
+        let fetched = removed.resolved.or(match removed.version {
+            ExtendedPackageVersion::Version(v) => Some(v),
+            ExtendedPackageVersion::Latest => None,
+        });
+        if let Some(fetched) = fetched {
+            self.sources.write().retain(|id, _| {
+                id.package().is_none_or(|spec| {
+                    !(spec.namespace == removed.namespace
+                        && spec.name == removed.name
+                        && spec.version == fetched)
+                })
+            });
+        }
+        self.package_index = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Downloads packages of namespaces starting with `namespace_prefix` from `url_template`,
+    /// e.g. `/packages/download?uname={scope}&pname={name}` for `wolframe-`. The template
+    /// may use `{namespace}`, `{scope}` (the namespace without the prefix), `{name}` and
+    /// `{version}`; without `{version}` the registry is expected to serve the newest version.
+    /// With `refetch_latest`, latest packages are downloaded again on every prefetch.
+    /// Registering a prefix again replaces it.
+    pub fn register_package_registry(
+        &mut self,
+        namespace_prefix: String,
+        url_template: String,
+        refetch_latest: Option<bool>,
+    ) -> Result<(), JsValue> {
+        if namespace_prefix.is_empty() {
+            return Err(JsValue::from_str(
+                "InvalidRegistry: namespace prefix must not be empty",
+            ));
+        }
+        if !url_template.contains("{name}") {
+            return Err(JsValue::from_str(
+                "InvalidRegistry: url template must contain {name}",
+            ));
+        }
+
+        self.registries.retain(|r| r.prefix != namespace_prefix);
+        self.registries.push(registry::Registry {
+            prefix: namespace_prefix,
+            url_template,
+            refetch_latest: refetch_latest.unwrap_or(false),
+        });
+
+        Ok(())
+    }
+
+    /// Makes the next `prefetch_packages` download a package again, e.g. to pick up the newest
+    /// version of a latest package. The cached files stay in use until then.
+    pub fn invalidate_package(&mut self, spec: RawPackageSpec) -> Result<(), JsValue> {
+        let version =
+            ExtendedPackageVersion::from_str(&spec.version).map_err(|e| JsValue::from_str(&e))?;
+
+        let mut lock = self.packages.write();
+        let package = lock
+            .iter_mut()
+            .find(|p| {
+                p.namespace == spec.namespace.as_str()
+                    && p.name == spec.name.as_str()
+                    && p.version == version
+            })
+            .ok_or_else(|| {
+                JsValue::from_str(&format!(
+                    "NotFound: package @{}/{}:{}",
+                    spec.namespace, spec.name, version
+                ))
+            })?;
+
+        package.fetched = false;
+        if package.is_latest() {
+            // Resolve the newest version again, re-downloading the preview index if needed
+            package.resolved = None;
+            self.index_downloaded.store(false, Ordering::SeqCst);
+        }
+        self.package_index = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Registers the newest version of every package in an `index.json` from
+    /// packages.typst.org so they show up in import autocompletion. Packages that
+    /// were already added keep their version and only gain a missing description.
+    pub fn load_package_index(&mut self, json: Vec<u8>) -> Result<(), JsValue> {
+        let entries = package_index::parse_index(&json)
+            .map_err(|e| JsValue::from_str(&format!("invalid package index: {}", e)))?;
+
+        let mut lock = self.packages.write();
+        for entry in entries {
+            self.latest_versions.write().insert(
+                (
+                    EcoString::from(package_index::PREVIEW_NAMESPACE),
+                    EcoString::from(entry.name.as_str()),
+                ),
+                entry.version,
+            );
+            let description = entry.description.map(EcoString::from);
+            let known = lock.iter_mut().find(|p| {
+                p.namespace == package_index::PREVIEW_NAMESPACE && p.name == entry.name.as_str()
+            });
+            match known {
+                Some(known) => {
+                    if known.description.is_none() {
+                        known.description = description;
+                    }
+                }
+                None => lock.push(PackageWrapper {
+                    description,
+                    ..PackageSpec {
+                        namespace: EcoString::from(package_index::PREVIEW_NAMESPACE),
+                        name: EcoString::from(entry.name),
+                        version: entry.version,
+                    }
+                    .into()
+                }),
+            }
+        }
+        self.package_index = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Serializes the registered packages with their resolved versions and content hashes.
+    pub fn generate_lockfile(&self) -> Result<String, JsValue> {
+        let packages = self
+            .packages
+            .read()
+            .iter()
+            .filter_map(PackageWrapper::to_locked)
+            .collect();
+
+        Lockfile::new(packages)
+            .to_json()
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Pins the registered packages to the versions (and hashes) recorded in a lockfile.
+    pub fn apply_lockfile(&mut self, data: String) -> Result<(), JsValue> {
+        let lockfile = Lockfile::from_json(&data).map_err(|e| JsValue::from_str(&e))?;
+
+        let mut lock = self.packages.write();
+        for locked in lockfile.packages {
+            let resolved =
+                PackageVersion::from_str(&locked.resolved).map_err(|e| JsValue::from_str(&e))?;
+            let version = ExtendedPackageVersion::from(resolved);
+
+            // Lock "latest" and exact matches onto the recorded version
+            let existing = lock.iter_mut().find(|p| {
+                p.namespace == locked.namespace
+                    && p.name == locked.name
+                    && (p.version == ExtendedPackageVersion::Latest || p.version == version)
+            });
+
+            match existing {
+                Some(package) => {
+                    if package.version != version || package.locked_hash != locked.hash {
+                        package.fetched = false;
+                    }
+                    package.version = version;
+                    package.locked_hash = locked.hash;
+                }
+                None => {
+                    let mut package: PackageWrapper = PackageSpec {
+                        namespace: locked.namespace.into(),
+                        name: locked.name.into(),
+                        version: resolved,
+                    }
+                    .into();
+                    package.locked_hash = locked.hash;
+                    lock.push(package);
+                }
+            }
+        }
+        drop(lock);
+
+        self.package_index = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Downloads every package the project imports so `compile` runs without network access.
+    /// Resolves to a `PrefetchReport`; failed packages are also reported when compiling.
+    pub fn prefetch_packages(&self) -> js_sys::Promise {
+        let prefetcher = prefetch::Prefetcher {
+            sources: self.sources.clone(),
+            packages: self.packages.clone(),
+            latest_versions: self.latest_versions.clone(),
+            index_downloaded: self.index_downloaded.clone(),
+            package_index_stale: self.package_index_stale.clone(),
+            registries: self.registries.clone(),
+            profiling: self.profiling.clone(),
+            limits: self.limits.clone(),
+        };
+
+        wasm_bindgen_futures::future_to_promise(
+            async move { Ok(prefetcher.prefetch().await.into()) },
+        )
     }
 
     // implement packages https://packages.typst.org/preview/index.json
+    /// Completions at `offset`. `explicit` (the default) is for completion requested by the
+    /// user, e.g. with Ctrl+Space; pass `false` when it was triggered by typing like `#` or
+    /// `.`, which offers fewer candidates in the middle of a word.
+    ///
+    /// `filter` keeps the completions whose label fuzzily matches it, best matches first,
+    /// and `max_results` caps their number before they cross into JS.
     pub fn autocomplete(
         &self,
         file: String,
         offset: usize,
+        explicit: Option<bool>,
+        filter: Option<String>,
+        max_results: Option<usize>,
     ) -> Result<Vec<js_types::Completion>, JsValue> {
-        let source = self
-            .source(FileId::new(None, VirtualPath::new(&file)))
-            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
-
-        let doc = self.last_doc.lock().unwrap().clone();
-
-        match typst_ide::autocomplete(self, doc.as_ref(), &source, offset, true) {
-            Some(completions) => Ok(completions.1.into_iter().map(|c| c.into()).collect()),
-            None => Ok(Vec::new()),
-        }
+        Ok(self
+            .autocomplete_with_range(file, offset, explicit, filter, max_results)?
+            .completions)
     }
 
-    pub fn definition(
+    /// `autocomplete` with the range the completions replace, from the start of the
+    /// partly typed word to `offset`.
+    pub fn autocomplete_with_range(
         &self,
         file: String,
         offset: usize,
-    ) -> Result<js_types::HoverProvider, JsValue> {
+        explicit: Option<bool>,
+        filter: Option<String>,
+        max_results: Option<usize>,
+    ) -> Result<js_types::CompletionResult, JsValue> {
         let source = self
             .source(FileId::new(None, VirtualPath::new(&file)))
             .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
 
-        let doc = self.last_doc.lock().unwrap().clone();
+        let doc = self.document_for(source.id());
+
+        // Without a document typst-ide knows no labels, fall back to the ones in the sources
+        if let Some(from) = labels::ref_start(&source, offset).filter(|_| doc.is_none()) {
+            return Ok(js_types::CompletionResult {
+                from,
+                to: offset,
+                completions: fuzzy::filter(
+                    labels::label_completions(&self.labels()?)
+                        .into_iter()
+                        // Broken bibliographies only cost their keys here
+                        .chain(bibliography::key_completions(
+                            self.bibliography_keys().unwrap_or_default(),
+                        ))
+                        .collect(),
+                    filter.as_deref(),
+                    max_results,
+                    |c| &c.label,
+                ),
+            });
+        }
 
-        let raw_def = typst_ide::definition(
+        let (mut from, mut completions) = match typst_ide::autocomplete(
             self,
             doc.as_ref(),
             &source,
             offset,
-            typst::syntax::Side::After,
-        );
+            explicit.unwrap_or(true),
+        ) {
+            Some((from, completions)) => (
+                from,
+                fuzzy::filter(completions, filter.as_deref(), max_results, |c| &c.label)
+                    .into_iter()
+                    .map(|c| c.into())
+                    .collect::<Vec<_>>(),
+            ),
+            None => (offset, Vec::new()),
+        };
 
-        let def = raw_def
-            .clone()
-            .map(|def| js_types::Definition::new(def, self.sources.read().unwrap().clone()));
+        // typst-ide doesn't know the project files, complete paths from the sources
+        if let Some((path_use, typed)) = paths::path_at(&source, offset) {
+            let vpath = source.id().vpath();
+            let entries = self.list_dir(paths::completion_dir(vpath, &typed), Some(false));
+            // Path completions replace the whole typed path
+            from = offset - typed.len();
+            completions.extend(fuzzy::filter(
+                paths::path_completions(entries, &typed, path_use, vpath),
+                filter.as_deref(),
+                max_results,
+                |c| &c.label,
+            ));
+            completions.truncate(max_results.unwrap_or(usize::MAX));
+        }
 
-        /* if raw_def.is_some() {
-            let def = raw_def.unwrap();
-            if def.kind == typst_ide::DefinitionKind::Function
-                || def.kind == typst_ide::DefinitionKind::Variable
-            {
-                let target = def.name_span;
+        Ok(js_types::CompletionResult {
+            from,
+            to: offset,
+            completions,
+        })
+    }
 
-                if !target.is_detached() {
-                    let file_id = target.id().expect("None detached span should have an id");
+    /// The `limit` best completions for `prefix`: labels starting with it first, then the
+    /// ones with a later word starting with it, then any fuzzy match. Ties keep the order
+    /// typst-ide proposed them in, `total` counts all matches.
+    pub fn autocomplete_filtered(
+        &self,
+        file: String,
+        offset: usize,
+        prefix: String,
+        limit: usize,
+    ) -> Result<js_types::FilteredCompletions, JsValue> {
+        let result = self.autocomplete_with_range(file, offset, None, None, None)?;
+        let (completions, total) = fuzzy::rank(result.completions, &prefix, limit, |c| &c.label);
+
+        Ok(js_types::FilteredCompletions {
+            from: result.from,
+            to: result.to,
+            total,
+            completions,
+        })
+    }
 
-                    let sources = self.sources.read().unwrap();
+    /// `autocomplete` with the result encoded as `format`, for large completion lists.
+    pub fn autocomplete_encoded(
+        &self,
+        file: String,
+        offset: usize,
+        format: js_types::OutputFormat,
+        explicit: Option<bool>,
+        filter: Option<String>,
+        max_results: Option<usize>,
+    ) -> Result<JsValue, JsValue> {
+        encoding::encode(
+            &self.autocomplete(file, offset, explicit, filter, max_results)?,
+            format,
+        )
+    }
 
-                    let entry = sources
-                        .get(&file_id)
-                        .expect("File should exist because it got compiled");
+    /// Searches the `sym` and `emoji` modules by dotted name, for an insert-symbol palette.
+    pub fn search_symbols(&self, query: String, limit: usize) -> Vec<js_types::SymbolInfo> {
+        symbols::search(self.library(), &query, limit)
+    }
 
-                    let source = entry.source();
+    /// Entry keys of the bibliography files loaded by the project, for `@key` and
+    /// `cite(<key>)` completion. Files that are missing or fail to parse are an error.
+    pub fn bibliography_keys(&self) -> Result<Vec<String>, JsValue> {
+        let files = bibliography::bibliography_files(&self.sources.read());
+
+        let mut keys = Vec::new();
+        for id in files {
+            let path = id.vpath().as_rooted_path().to_string_lossy().into_owned();
+            let data = self
+                .file(id)
+                .map_err(|e| JsValue::from_str(&format!("FileError: {}: {}", path, e)))?;
+            for key in bibliography::parse_keys(&path, &data)
+                .map_err(|e| JsValue::from_str(&format!("InvalidBibliography: {}: {}", path, e)))?
+            {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        Ok(keys)
+    }
 
-                    let node = source.find(target).unwrap().parent().unwrap().clone();
-                    logWasm(&format!(
-                        "node: {:?}, {:?}, {:?}",
-                        node.kind(),
+    /// Warnings for citations in `file` whose key is in none of the bibliographies and is
+    /// not a label either. Empty if the project loads no bibliography.
+    pub fn check_citations(&self, file: String) -> Result<Vec<js_types::Diagnostics>, JsValue> {
+        let source = self.text_source(&file)?;
+        if bibliography::bibliography_files(&self.sources.read()).is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys = self
+            .bibliography_keys()?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let labels = self
+            .labels()?
+            .into_iter()
+            .map(|label| label.name)
+            .collect::<HashSet<_>>();
+        Ok(bibliography::unknown_citations(&source, &keys, &labels))
+    }
+
+    /// The bindings of the standard library, or of one of its modules like `calc` or
+    /// `math`, for a documentation browser. Listed once per module and cached.
+    pub fn library_symbols(
+        &self,
+        module: Option<String>,
+    ) -> Result<Vec<js_types::LibrarySymbol>, JsValue> {
+        let key = module.clone().unwrap_or_default();
+        if let Some(symbols) = self.library_symbols.lock().get(&key) {
+            return Ok(symbols.clone());
+        }
+
+        let symbols = symbols::library_symbols(self.library(), module.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("InvalidModule: {}", e)))?;
+        self.library_symbols.lock().insert(key, symbols.clone());
+        Ok(symbols)
+    }
+
+    /// Labels for `@` reference completion, taken from the last compiled document so labels
+    /// added by show rules are included. Before the first compile they are read from the sources.
+    pub fn labels(&self) -> Result<Vec<js_types::LabelInfo>, JsValue> {
+        let sources = self.sources.read();
+        Ok(match self.last_doc.lock().as_ref() {
+            Some(doc) => labels::document_labels(doc, &sources),
+            None => labels::source_labels(&sources),
+        })
+    }
+
+    /// Every use of the symbol or label at `offset` in the project and its packages,
+    /// including the definition itself.
+    pub fn references(
+        &self,
+        file: String,
+        offset: usize,
+    ) -> Result<Vec<js_types::Reference>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        let doc = self.document_for(source.id());
+        let sources = self.sources.read().clone();
+
+        Ok(references::find_references(
+            self,
+            doc.as_ref(),
+            &sources,
+            &source,
+            offset,
+        ))
+    }
+
+    /// Jumps from a reference (`@intro`) to its label (`<intro>`), or from a label to its
+    /// first reference. Duplicate labels resolve to the first one with a warning.
+    pub fn goto_label(
+        &self,
+        file: String,
+        offset: usize,
+    ) -> Result<Option<js_types::ResolvedSpan>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        let sources = self.sources.read().clone();
+        let targets = references::label_jump(self, &sources, &source, offset);
+
+        if targets.len() > 1 && targets[0].is_definition {
+            logging::log(LogLevel::Warn, || {
+                format!(
+                    "label declared {} times, jumping to the first in {}",
+                    targets.len(),
+                    targets[0].span.file_path
+                )
+            });
+        }
+
+        Ok(targets.into_iter().next().map(|target| target.span))
+    }
+
+    /// The `let` bindings of a file with their kind and tidy docs. Bindings inside blocks,
+    /// closures and loops are included with `module_scope` unset, `import` skips them.
+    pub fn module_exports(&self, file: String) -> Result<Vec<js_types::ExportedSymbol>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        Ok(exports::module_exports(&source))
+    }
+
+    /// Structured tidy docs of the function or variable at `offset`, read from the doc
+    /// comments of its binding. Builtin functions get their reference docs.
+    pub fn doc_comment(
+        &self,
+        file: String,
+        offset: usize,
+    ) -> Result<Option<js_types::TidyDocs>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        let doc = self.document_for(source.id());
+        let Some(def) = typst_ide::definition(
+            self,
+            doc.as_ref(),
+            &source,
+            offset,
+            typst::syntax::Side::After,
+        ) else {
+            return Ok(None);
+        };
+        if !matches!(
+            def.kind,
+            typst_ide::DefinitionKind::Function | typst_ide::DefinitionKind::Variable
+        ) {
+            return Ok(None);
+        }
+
+        let sources = self.sources.read();
+        let node = def
+            .name_span
+            .id()
+            .and_then(|id| sources.get(&id))
+            .and_then(|entry| Some(entry.source.find(def.name_span)?.parent()?.clone()));
+        if let Some(node) = node {
+            return Ok(Some(parse_doc_str(
+                def.name.to_string(),
+                collect_tidy_doc(node),
+            )));
+        }
+
+        Ok(match &def.value {
+            Some(typst::foundations::Value::Func(func)) => builtin_docs(def.name.to_string(), func),
+            _ => None,
+        })
+    }
+
+    pub fn definition(
+        &self,
+        file: String,
+        offset: usize,
+    ) -> Result<js_types::HoverProvider, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        let doc = self.document_for(source.id());
+
+        let raw_def = typst_ide::definition(
+            self,
+            doc.as_ref(),
+            &source,
+            offset,
+            typst::syntax::Side::After,
+        );
+
+        let def = raw_def.clone().map(|def| {
+            js_types::Definition::new(def, self.sources.read().clone(), &self.doc_options)
+        });
+
+        /* if raw_def.is_some() {
+            let def = raw_def.unwrap();
+            if def.kind == typst_ide::DefinitionKind::Function
+                || def.kind == typst_ide::DefinitionKind::Variable
+            {
+                let target = def.name_span;
+
+                if !target.is_detached() {
+                    let file_id = target.id().expect("None detached span should have an id");
+
+                    let sources = self.sources.read();
+
+                    let entry = sources
+                        .get(&file_id)
+                        .expect("File should exist because it got compiled");
+
+                    let source = entry.source();
+
+                    let node = source.find(target).unwrap().parent().unwrap().clone();
+                    logWasm(&format!(
+                        "node: {:?}, {:?}, {:?}",
+                        node.kind(),
                         node.index(),
                         node.offset()
                     ));
@@ -443,15 +1566,45 @@ impl SuiteCore {
         Ok(js_types::HoverProvider::new(def, tt))
     }
 
-    fn compile_str(&mut self, text: String) -> Result<String, JsValue> {
+    /// Sets the reserved namespace scratch compiles of `compile_str` live in.
+    /// Files in it are hidden from `get_files` and can't be added by the user.
+    pub fn set_scratch_namespace(&mut self, prefix: String) -> Result<(), JsValue> {
+        let prefix = VirtualPath::new(&prefix)
+            .as_rooted_path()
+            .to_string_lossy()
+            .into_owned();
+        if prefix == "/" {
+            return Err(JsValue::from_str(
+                "The scratch namespace can not be the project root.",
+            ));
+        }
+
+        self.scratch_prefix = prefix;
+        Ok(())
+    }
+
+    /// Compiles a standalone text and returns the first page as SVG. The text is
+    /// compiled from a temporary file in the scratch namespace, the project is left untouched.
+    pub fn compile_str(&mut self, text: String) -> Result<String, JsValue> {
         self.reset();
 
-        self.sources.write().unwrap().insert(
-            FileId::new(None, VirtualPath::new("/main.typ")),
-            FileEntry::new(FileId::new(None, VirtualPath::new("/main.typ")), text),
+        self.scratch_counter += 1;
+        let id = FileId::new(
+            None,
+            VirtualPath::new(format!(
+                "{}/{}.typ",
+                self.scratch_prefix, self.scratch_counter
+            )),
         );
+        self.sources.write().insert(id, FileEntry::new(id, text));
 
-        match typst::compile(self).output {
+        let previous_entrypoint = self.entrypoint.replace(id);
+        let output = typst::compile(self).output;
+        self.refresh_package_index();
+        self.entrypoint = previous_entrypoint;
+        self.sources.write().remove(&id);
+
+        match output {
             Ok(doc) => Ok(typst_svg::svg(&doc.pages[0])),
             Err(err) => {
                 let mut str = String::new();
@@ -476,66 +1629,976 @@ impl SuiteCore {
         }
     }
 
+    /// Registers a `(stage, done, total) => void` callback invoked during `compile()`.
+    /// Stages are "start", "parse", "layout", "render" (once per page) and "done".
+    /// `diagnostics()` reports "start", "parse" and "done" only, other calls report nothing.
+    pub fn set_progress_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.progress = callback.map(progress::ProgressCallback::new);
+    }
+
+    /// Records how long the phases of each compile take, including package fetches since
+    /// the previous compile. Read the report of the last compile with `last_profile`.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        if enabled != self.profiling.is_some() {
+            self.profiling = enabled.then(profile::FetchPhases::default);
+        }
+    }
+
+    pub fn last_profile(&self) -> Option<js_types::ProfileReport> {
+        self.last_profile.clone()
+    }
+
+    /// Sets which internal logs reach `logWasm`: "off", "error", "warn" (the default),
+    /// "info", "debug" or "trace". Events passed to `emitEvent` are not affected.
+    pub fn set_log_level(&self, level: String) -> Result<(), JsValue> {
+        let level = LogLevel::from_str(&level).map_err(|e| JsValue::from_str(&e))?;
+        logging::set_level(level);
+        Ok(())
+    }
+
+    /// Pins the clock used by `datetime.today()` to a unix timestamp in milliseconds.
+    /// Passing `undefined` goes back to the system clock.
+    pub fn set_now(&mut self, unix_millis: Option<f64>) -> Result<(), JsValue> {
+        self.clock = match unix_millis {
+            Some(millis) => Some(
+                DateTime::from_timestamp_millis(millis as i64)
+                    .ok_or(JsValue::from_str("timestamp out of range"))?
+                    .with_timezone(&Local)
+                    .fixed_offset(),
+            ),
+            None => None,
+        };
+        self.now = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Pins the clock used by `datetime.today()` to an RFC 3339 timestamp like
+    /// `2024-05-01T09:30:00+05:30`. Its UTC offset replaces the local timezone.
+    pub fn set_now_iso(&mut self, iso8601: String) -> Result<(), JsValue> {
+        let now = DateTime::parse_from_rfc3339(&iso8601)
+            .map_err(|e| JsValue::from_str(&format!("invalid timestamp: {}", e)))?;
+        self.clock = Some(now);
+        self.now = OnceLock::default();
+
+        Ok(())
+    }
+
+    /// Pins `datetime.today()` to a date, as midnight UTC. Like the other clock setters it
+    /// survives recompiles until `set_now(undefined)` goes back to the system clock.
+    pub fn set_today(&mut self, year: i32, month: u8, day: u8) -> Result<(), JsValue> {
+        let now = NaiveDate::from_ymd_opt(year, month.into(), day.into())
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .ok_or(JsValue::from_str("invalid date"))?
+            .and_utc()
+            .fixed_offset();
+        self.clock = Some(now);
+        self.now = OnceLock::from(now);
+
+        Ok(())
+    }
+
+    pub fn has_main(&self) -> bool {
+        self.sources.read().contains_key(&self.main())
+    }
+
     pub fn compile(&mut self, single: bool) -> Result<Vec<String>, Vec<js_types::Diagnostics>> {
-        match typst::compile(self).output {
+        self.compile_with(js_types::RenderOptions {
+            merged: single,
+            ..Default::default()
+        })
+    }
+
+    /// Compiles with `path` as the main file for this call only, e.g. to preview a chapter on
+    /// its own. The root is left untouched and the pages of the main preview keep counting as
+    /// rendered for `compile_changes`. IDE features in the files it uses read its document.
+    pub fn compile_file(
+        &mut self,
+        path: String,
+        single: bool,
+    ) -> Result<Vec<String>, Vec<js_types::Diagnostics>> {
+        let id = FileId::new(None, VirtualPath::new(&path));
+        if !self.sources.read().contains_key(&id) {
+            return Err(vec![js_types::Diagnostics::error(format!(
+                "NotFound: file '{}'",
+                path
+            ))]);
+        }
+
+        let previous_entrypoint = self.entrypoint.replace(id);
+        let page_hashes = std::mem::take(&mut self.page_hashes);
+        // The chapter is only kept in `entry_docs`, the rest of the API keeps the main document
+        let last_doc = self.last_doc.get_mut().take();
+        let result = self.compile(single);
+        self.entrypoint = previous_entrypoint;
+        self.page_hashes = page_hashes;
+        *self.last_doc.get_mut() = last_doc;
+        result
+    }
+
+    /// Compiles without rendering and returns the errors followed by the warnings, for live
+    /// error checking. The document is kept for introspection, rendered pages are untouched.
+    /// In strict mode the warnings are marked `promoted`.
+    pub fn diagnostics(&mut self) -> Vec<js_types::Diagnostics> {
+        if !self.has_main() {
+            return vec![js_types::Diagnostics::error(self.missing_main_message())];
+        }
+        if self.is_cancelled() {
+            return vec![js_types::Diagnostics::cancelled()];
+        }
+
+        self.now = OnceLock::default();
+        self.dependencies.lock().clear();
+        self.start_deadline();
+        self.report_progress(|p| p.start());
+        self.compiling = true;
+        let warned = typst::compile(self);
+        self.compiling = false;
+        self.report_progress(|p| p.report("done", 0, 0));
+        self.refresh_package_index();
+        self.missing_fonts = fonts::missing_from_warnings(&warned.warnings);
+
+        if self.is_cancelled() {
+            return vec![js_types::Diagnostics::cancelled()];
+        }
+        if let Some(exceeded) = self.time_limit_exceeded() {
+            return vec![js_types::Diagnostics::error(exceeded.to_string())];
+        }
+
+        let errors = match warned.output {
             Ok(doc) => {
-                *self.last_doc.lock().unwrap() = Some(doc.clone());
-                if single {
-                    Ok(vec![typst_svg::svg_merged(&doc, Abs::cm(2.0))])
-                } else {
-                    Ok(doc.pages.iter().map(typst_svg::svg).collect())
-                }
+                self.remember_doc(&doc);
+                *self.last_doc.lock() = Some(doc);
+                Default::default()
             }
-            Err(err) => {
-                let mut errs: Vec<js_types::Diagnostics> = Vec::new();
+            Err(errors) => errors,
+        };
+        let sources = self.sources.read().clone();
+        let mut diagnostics = errors
+            .into_iter()
+            .map(|diag| js_types::Diagnostics::from_diag(diag, sources.clone(), self.library()))
+            .collect::<Vec<_>>();
+        diagnostics.extend(warned.warnings.into_iter().map(|diag| {
+            let mut warning =
+                js_types::Diagnostics::from_diag(diag, sources.clone(), self.library());
+            warning.promoted = self.strict;
+            warning
+        }));
+        diagnostics
+    }
 
-                for diag in err {
-                    errs.push(js_types::Diagnostics::from_diag(
-                        diag,
-                        self.sources.read().unwrap().clone(),
-                    ));
-                }
+    /// Compiles the document to HTML, needs the `Html` target from `set_target`. The HTML
+    /// target and the library `Features` selecting it were added in typst 0.13, this build
+    /// uses typst 0.12 and always fails with an `Unsupported` diagnostic.
+    pub fn compile_html(&self) -> Result<String, Vec<js_types::Diagnostics>> {
+        if self.target != js_types::Target::Html {
+            return Err(vec![js_types::Diagnostics::error(
+                "InvalidTarget: compile_html needs the html target, call set_target first"
+                    .to_string(),
+            )]);
+        }
+        Err(vec![html_unsupported()])
+    }
 
-                Err(errs)
+    /// Token to cancel compiles with, created on first use. typst itself cannot be interrupted,
+    /// so the token is checked before compiling, whenever typst loads a file during the
+    /// compile, after it finished and between rendered pages. A single long evaluation or
+    /// layout still runs to its end (typst bails out of loops after 10000 iterations).
+    pub fn cancellation_token(&mut self) -> cancel::CancellationToken {
+        self.cancellation
+            .get_or_insert_with(Default::default)
+            .clone()
+    }
+
+    /// `compile` with a `data-span="path:start:end"` attribute (byte offsets) on every text
+    /// group and shape of the SVG that stems from a loaded source, for hit testing in the
+    /// preview without calling back into wasm. The SVG grows to about twice its size.
+    pub fn compile_svg_annotated(
+        &mut self,
+        single: bool,
+    ) -> Result<Vec<String>, Vec<js_types::Diagnostics>> {
+        self.compile_with(js_types::RenderOptions {
+            merged: single,
+            annotate_spans: true,
+            ..Default::default()
+        })
+    }
+
+    /// `compile` with control over how the pages are rendered to SVG.
+    pub fn compile_with(
+        &mut self,
+        opts: js_types::RenderOptions,
+    ) -> Result<Vec<String>, Vec<js_types::Diagnostics>> {
+        self.compile_pages(opts, false)
+            .map(|compiled| compiled.pages)
+    }
+
+    /// `compile` with a page size in pt as if the main file started with
+    /// `#set page(width: .., height: ..)`, for previewing at another size. Page set rules of
+    /// the document still win, the sources are not touched.
+    pub fn compile_with_page(
+        &mut self,
+        width_pt: f64,
+        height_pt: f64,
+        single: bool,
+    ) -> Result<Vec<String>, Vec<js_types::Diagnostics>> {
+        for (name, size) in [("width_pt", width_pt), ("height_pt", height_pt)] {
+            if !(size.is_finite() && size > 0.0) {
+                return Err(vec![js_types::Diagnostics::error(format!(
+                    "InvalidSize: {} must be a positive number, got {}",
+                    name, size
+                ))]);
             }
         }
+
+        if self.page_size != Some((width_pt, height_pt)) {
+            self.page_size = Some((width_pt, height_pt));
+            self.sized_library = OnceLock::default();
+        }
+
+        self.sized_compile = true;
+        let result = self.compile(single);
+        self.sized_compile = false;
+        result
     }
 
+    /// `compile_with` that also reports which pages differ from the previous compile. With
+    /// `skip_unchanged`, unchanged pages are not rendered and left as empty strings. A merged SVG is
+    /// only skipped when no page changed.
+    pub fn compile_changes(
+        &mut self,
+        opts: js_types::RenderOptions,
+        skip_unchanged: bool,
+    ) -> Result<js_types::ChangedPages, Vec<js_types::Diagnostics>> {
+        self.compile_pages(opts, skip_unchanged)
+    }
+
+    /// Renders pages `[start, end)` of the last compiled document as `compile` would,
+    /// clamped to the pages that exist.
+    pub fn render_pages(
+        &self,
+        start: usize,
+        end: usize,
+        merged: bool,
+    ) -> Result<js_types::RenderedPages, JsValue> {
+        if end < start {
+            return Err(JsValue::from_str(&format!(
+                "InvalidRange: end {} is before start {}",
+                end, start
+            )));
+        }
+
+        let doc = self.last_doc.lock();
+        let doc = doc
+            .as_ref()
+            .ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+        let end = end.min(doc.pages.len());
+        let start = start.min(end);
+
+        let pages = if start == end {
+            Vec::new()
+        } else if merged {
+            let range = Document {
+                pages: doc.pages[start..end]
+                    .iter()
+                    .map(|page| self.preview.apply(page))
+                    .collect(),
+                ..doc.clone()
+            };
+            vec![typst_svg::svg_merged(&range, Abs::cm(2.0))]
+        } else {
+            doc.pages[start..end]
+                .iter()
+                .map(|page| typst_svg::svg(&self.preview.apply(page)))
+                .collect()
+        };
+
+        Ok(js_types::RenderedPages { start, end, pages })
+    }
+
+    /// The source location under a click at (`x_pt`, `y_pt`) on a page of the last compiled
+    /// document, or nearest to it when the click hit no text. `None` if the page shows
+    /// nothing that stems from a loaded source.
+    pub fn source_at_position(
+        &self,
+        page: usize,
+        x_pt: f64,
+        y_pt: f64,
+    ) -> Result<Option<js_types::ResolvedSpan>, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+        let frame = &doc
+            .pages
+            .get(page)
+            .ok_or_else(|| {
+                JsValue::from_str(&format!(
+                    "InvalidPage: page {} out of range, document has {} pages",
+                    page,
+                    doc.pages.len()
+                ))
+            })?
+            .frame;
+
+        let click = typst::layout::Point::new(Abs::pt(x_pt), Abs::pt(y_pt));
+        let jump = typst_ide::jump_from_click(self, &doc, frame, click);
+        let sources = self.sources.read();
+        if let Some(typst_ide::Jump::Source(id, offset)) = jump {
+            let leaf = sources.get(&id).and_then(|entry| {
+                LinkedNode::new(entry.source.root())
+                    .leaf_at(offset, typst::syntax::Side::After)
+                    .map(|leaf| (leaf.span(), entry.source.clone()))
+            });
+            if let Some((span, source)) = leaf {
+                return Ok(Some(js_types::ResolvedSpan::from_source(span, &source)));
+            }
+        }
+
+        Ok(frames::nearest_span(frame, click)
+            .filter(|span| span.id().is_some_and(|id| sources.contains_key(&id)))
+            .map(|span| js_types::ResolvedSpan::from_sources(span, &sources)))
+    }
+
+    /// Where the text at `offset` was laid out in the last compiled document, the first
+    /// place if it appears several times. `None` if the cursor is not in text or the text is
+    /// not shown.
+    pub fn jump_to_preview(
+        &self,
+        file: String,
+        offset: usize,
+    ) -> Result<Option<js_types::PreviewPosition>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        Ok(typst_ide::jump_from_cursor(&doc, &source, offset)
+            .first()
+            .map(|position| js_types::PreviewPosition {
+                page: position.page.get() - 1,
+                x_pt: position.point.x.to_pt(),
+                y_pt: position.point.y.to_pt(),
+            }))
+    }
+
+    /// Size of every page of the last compiled document, to lay out the SVGs before they load.
+    pub fn page_sizes(&self) -> Result<Vec<js_types::PageSize>, JsValue> {
+        let doc = self.last_doc.lock();
+        let doc = doc
+            .as_ref()
+            .ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        Ok(doc
+            .pages
+            .iter()
+            .map(|page| js_types::PageSize {
+                width_pt: page.frame.width().to_pt(),
+                height_pt: page.frame.height().to_pt(),
+            })
+            .collect())
+    }
+
+    /// Runs a selector like `<key>` or `heading.where(level: 1)` against the last compiled
+    /// document, like `typst query`. Returns the matches (or their `field`) as a JSON string.
+    pub fn query(&self, selector: String, field: Option<String>) -> Result<String, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        query::query(self, &doc, &selector, field.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("QueryError: {}", e)))
+    }
+
+    /// Rasterizes a single page of the last compiled document to an encoded PNG.
+    pub fn render_png(&self, page: usize, pixels_per_pt: f32) -> Result<Vec<u8>, JsValue> {
+        if !(pixels_per_pt > 0.0 && pixels_per_pt <= MAX_PIXELS_PER_PT) {
+            return Err(JsValue::from_str(&format!(
+                "InvalidScale: pixels_per_pt must be in (0, {}], got {}",
+                MAX_PIXELS_PER_PT, pixels_per_pt
+            )));
+        }
+
+        let doc = self.last_doc.lock();
+        let doc = doc
+            .as_ref()
+            .ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+        let page = doc.pages.get(page).ok_or_else(|| {
+            JsValue::from_str(&format!(
+                "InvalidPage: page {} out of range, document has {} pages",
+                page,
+                doc.pages.len()
+            ))
+        })?;
+
+        typst_render::render(&self.preview.apply(page), pixels_per_pt)
+            .encode_png()
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+    }
+
+    /// Adds a project file. A leading byte order mark is not part of its source, see `has_bom`.
     pub fn add_file(&mut self, file: String, text: String) -> Result<(), JsValue> {
-        logWasm(format!("adding file: {:?}", file).as_str());
+        logging::log(LogLevel::Debug, || format!("adding file: {:?}", file));
         let id = FileId::new(None, VirtualPath::new(&file));
+        if self.is_scratch(id) {
+            return Err(JsValue::from_str(&format!(
+                "InvalidPath: '{}' lies in the reserved scratch namespace '{}'",
+                file, self.scratch_prefix
+            )));
+        }
+        self.check_file_limits(id, &file, text.len())
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
         self.sources
             .write()
-            .unwrap()
             .insert(id, FileEntry::new(id, text.clone()));
 
         Ok(())
     }
 
+    /// Current text of a file, package files are addressed as `/@namespace/name:version/path`.
+    pub fn get_file(&self, path: String) -> Result<String, JsValue> {
+        let id = paths::parse_qualified_path(&path);
+        let sources = self.sources.read();
+        let entry = sources
+            .get(&id)
+            .ok_or_else(|| JsValue::from_str(&format!("NotFound: file '{}'", path)))?;
+        if !entry.is_utf8() {
+            return Err(JsValue::from_str(&format!(
+                "InvalidUtf8: '{}' is a binary file",
+                path
+            )));
+        }
+
+        Ok(entry.source.text().to_string())
+    }
+
+    /// Whether a file starts with a byte order mark. It is stripped before parsing, so
+    /// `get_file` returns the text without it and all offsets and ranges count from after it.
+    /// Hosts keeping it in their buffer shift them by its length, one UTF-16 code unit or
+    /// three bytes. Data loading functions like `csv` still read it.
+    pub fn has_bom(&self, path: String) -> Result<bool, JsValue> {
+        let id = paths::parse_qualified_path(&path);
+        self.sources
+            .read()
+            .get(&id)
+            .map(FileEntry::has_bom)
+            .ok_or_else(|| JsValue::from_str(&format!("NotFound: file '{}'", path)))
+    }
+
+    /// Replaces the whole text of an existing file in place, so unchanged parts keep their
+    /// spans and are reparsed incrementally. Returns the reparsed range.
+    pub fn set_file(&mut self, file: String, text: String) -> Result<js_types::TextRange, JsValue> {
+        let id = FileId::new(None, VirtualPath::new(&file));
+        self.check_file_limits(id, &file, text.len())
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        let mut sources = self.sources.write();
+        let entry = sources
+            .get_mut(&id)
+            .ok_or_else(|| JsValue::from_str(&format!("NotFound: file '{}'", file)))?;
+
+        // Binary files have no source to reparse
+        if !entry.is_utf8() {
+            let len = text.len();
+            *entry = FileEntry::new(id, text);
+            return Ok((0..len).into());
+        }
+
+        Ok(entry.replace(&text).into())
+    }
+
+    /// Sets `sys.inputs` from an object of string values or an array of `[key, value]`
+    /// pairs, like `--input key=value` on the command line. The next compile uses a library
+    /// rebuilt with them, `reset` keeps them.
+    pub fn set_inputs(&mut self, inputs: JsValue) -> Result<(), JsValue> {
+        let inputs = if js_sys::Array::is_array(&inputs) {
+            serde_wasm_bindgen::from_value::<Vec<(String, String)>>(inputs)
+                .map(|pairs| pairs.into_iter().collect::<BTreeMap<_, _>>())
+        } else {
+            serde_wasm_bindgen::from_value::<BTreeMap<String, String>>(inputs)
+        }
+        .map_err(|e| {
+            JsValue::from_str(&format!(
+                "InvalidInputs: expected an object of strings or [key, value] pairs ({})",
+                e
+            ))
+        })?;
+
+        if self.inputs != inputs {
+            self.inputs = inputs;
+            self.reset_library();
+        }
+
+        Ok(())
+    }
+
+    /// How documentation in `definition` results is rendered, markdown is on by default.
+    pub fn set_doc_options(&mut self, options: js_types::DocOptions) {
+        self.doc_options = options;
+    }
+
+    /// Styles the rendered preview for dark themes: `page_fill` (a hex color or
+    /// "transparent") replaces the background of every page and `text_invert` flips the
+    /// lightness of text. Only SVG and PNG rendering is affected, not the compiled document
+    /// that queries, fingerprints and exports see.
+    pub fn set_render_options(&mut self, options: JsValue) -> Result<(), JsValue> {
+        let options: js_types::PreviewOptions = serde_wasm_bindgen::from_value(options)
+            .map_err(|e| JsValue::from_str(&format!("InvalidOptions: {}", e)))?;
+        self.preview =
+            preview::PreviewStyle::from_options(&options).map_err(|e| JsValue::from_str(&e))?;
+
+        Ok(())
+    }
+
+    /// Selects whether the document is compiled to pages with `compile` or to HTML with
+    /// `compile_html`, the other method fails. The library is rebuilt for the new target, in
+    /// typst 0.12 it has no HTML features yet so `Html` always fails with `Unsupported`.
+    pub fn set_target(&mut self, target: js_types::Target) {
+        if self.target != target {
+            self.target = target;
+            self.reset_library();
+        }
+    }
+
+    /// Makes compiles fail when typst reports warnings, e.g. for unresolved references or
+    /// deprecated calls. The warnings are returned with the errors, marked as `promoted`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Registers packages imported by the sources that were not added with `add_packages`, as
+    /// long as they are from @preview or a registered custom registry. They still have to be
+    /// downloaded with `prefetch_packages()` before the compile succeeds.
+    pub fn set_auto_register_packages(&mut self, enabled: bool) {
+        self.auto_register_packages = enabled;
+    }
+
+    /// Limits applied to added files, fetched package archives and compile time. Files already
+    /// present are kept. By default single files are limited to 8 MiB and archives to 64 MiB
+    /// decompressed, compiles have no time limit.
+    pub fn set_resource_limits(&mut self, limits: js_types::ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// Largest accepted project or package file in bytes, `None` lifts the limit. Files over it
+    /// are refused by `add_file`, `set_file`, `edit` and package extraction.
+    pub fn set_max_file_size(&mut self, bytes: Option<usize>) {
+        self.limits.max_file_size = bytes;
+    }
+
+    pub fn resource_limits(&self) -> js_types::ResourceLimits {
+        self.limits.clone()
+    }
+
+    pub fn resource_usage(&self) -> js_types::ResourceUsage {
+        let sources = self.sources.read();
+        let mut usage = js_types::ResourceUsage {
+            project_files: 0,
+            project_size: 0,
+            largest_file: 0,
+            package_files: 0,
+            package_size: 0,
+        };
+
+        for (id, entry) in sources.iter() {
+            let size = entry.bytes().len();
+            if id.package().is_some() {
+                usage.package_files += 1;
+                usage.package_size += size;
+            } else if !self.is_scratch(*id) {
+                usage.project_files += 1;
+                usage.project_size += size;
+                usage.largest_file = usage.largest_file.max(size);
+            }
+        }
+        usage
+    }
+
     pub fn remove_file(&mut self, file: String) -> Result<(), JsValue> {
         let id = FileId::new(None, VirtualPath::new(&file));
-        self.sources.write().unwrap().remove(&id);
+        self.sources.write().remove(&id);
+
+        Ok(())
+    }
+
+    /// Moves a file. With `update_references`, import, include and file loading paths
+    /// pointing at the file (and the moved file's own relative paths) are rewritten,
+    /// keeping each call site's rooted or relative style. Edits for the moved file use
+    /// its new path. With `dry_run` nothing is changed and only the edits are returned.
+    pub fn move_file(
+        &mut self,
+        old: String,
+        new: String,
+        update_references: bool,
+        dry_run: bool,
+    ) -> Result<Vec<js_types::FileTextEdit>, JsValue> {
+        let old_id = FileId::new(None, VirtualPath::new(&old));
+        let new_id = FileId::new(None, VirtualPath::new(&new));
+        if !self.sources.read().contains_key(&old_id) {
+            return Err(JsValue::from_str(&format!("NotFound: '{}'", old)));
+        }
+
+        self.move_entries(
+            HashMap::from([(old_id, new_id)]),
+            update_references,
+            dry_run,
+        )
+    }
+
+    /// Moves every project file under the directory `old` to `new`, like `move_file`.
+    /// Paths pointing at any of them are rewritten with `update_references`.
+    pub fn move_directory(
+        &mut self,
+        old: String,
+        new: String,
+        update_references: bool,
+        dry_run: bool,
+    ) -> Result<Vec<js_types::FileTextEdit>, JsValue> {
+        let old_dir = VirtualPath::new(&old);
+        let new_dir = VirtualPath::new(&new);
+        let moves = self
+            .sources
+            .read()
+            .keys()
+            .filter(|id| id.package().is_none() && !self.is_scratch(**id))
+            .filter_map(|id| {
+                let rest = id
+                    .vpath()
+                    .as_rooted_path()
+                    .strip_prefix(old_dir.as_rooted_path())
+                    .ok()?;
+                Some((
+                    *id,
+                    FileId::new(None, VirtualPath::new(new_dir.as_rooted_path().join(rest))),
+                ))
+            })
+            .collect::<HashMap<_, _>>();
+        if moves.is_empty() {
+            return Err(JsValue::from_str(&format!("NotFound: '{}'", old)));
+        }
+
+        self.move_entries(moves, update_references, dry_run)
+    }
+
+    /// Moves the entries `moves` maps from old to new ids and applies the reference edits.
+    fn move_entries(
+        &mut self,
+        moves: HashMap<FileId, FileId>,
+        update_references: bool,
+        dry_run: bool,
+    ) -> Result<Vec<js_types::FileTextEdit>, JsValue> {
+        let edits = {
+            let sources = self.sources.read();
+            // Files that are moved away themselves make room
+            if let Some(taken) = moves.iter().find(|(old, new)| {
+                old != new && sources.contains_key(new) && !moves.contains_key(new)
+            }) {
+                return Err(JsValue::from_str(&format!(
+                    "AlreadyExists: '{}'",
+                    taken.1.vpath().as_rooted_path().display()
+                )));
+            }
+
+            if update_references {
+                self.reference_edits(&sources, &moves)
+            } else {
+                Vec::new()
+            }
+        };
+        if dry_run {
+            return Ok(edits);
+        }
+
+        let mut sources = self.sources.write();
+        // Rebuilt from the bytes so the source gets the new id, binary files and byte order
+        // marks are kept
+        let moved = moves
+            .iter()
+            .filter_map(|(old, new)| Some((*new, sources.remove(old)?)))
+            .collect::<Vec<_>>();
+        for (id, entry) in moved {
+            sources.insert(id, FileEntry::from_bytes(id, entry.bytes().to_vec()));
+        }
+
+        // Back to front so earlier offsets stay valid
+        for edit in edits.iter().rev() {
+            let id = FileId::new(None, VirtualPath::new(&edit.file_path));
+            if let Some(entry) = sources.get_mut(&id) {
+                entry.edit(edit.start_offset..edit.end_offset, &edit.insert_text);
+            }
+        }
+
+        Ok(edits)
+    }
+
+    /// Which project files import or include which, with the cycles among them. Paths are
+    /// resolved against the importing file, imports of missing files are listed as well.
+    pub fn import_graph(&self) -> js_types::ImportGraph {
+        graph::import_graph(&self.import_edges())
+    }
+
+    /// The project files that import `file` directly or through other files, e.g. to warn
+    /// before removing it.
+    pub fn dependents(&self, file: String) -> Vec<String> {
+        let path = VirtualPath::new(&file)
+            .as_rooted_path()
+            .to_string_lossy()
+            .into_owned();
+        graph::dependents(&self.import_edges(), &path)
+    }
+
+    /// The import statements of a file with their imported names. Project files that exist
+    /// are resolved to their path, package imports carry the package spec and whether the
+    /// package is registered, e.g. to offer installing a missing one.
+    pub fn imports(&self, file: String) -> Result<Vec<js_types::ImportInfo>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        let mut imports = paths::imports(&source, &self.sources.read());
+        let packages = self.packages.read();
+        for import in &mut imports {
+            let Some(spec) = &import.package_spec else {
+                continue;
+            };
+            import.registered = packages.iter().any(|package| {
+                package.namespace == spec.namespace
+                    && package.name == spec.name
+                    && (package.version == ExtendedPackageVersion::Latest
+                        || package.version.to_string() == spec.version)
+            });
+        }
+
+        Ok(imports)
+    }
+
+    /// Converts a UTF-16 offset, as used by JS strings, into the byte offset every other
+    /// method expects. Offsets past the end are refused.
+    pub fn utf16_to_byte(&self, file: String, offset: usize) -> Result<usize, JsValue> {
+        self.text_source(&file)?
+            .utf16_to_byte(offset)
+            .ok_or_else(|| {
+                JsValue::from_str(&format!(
+                    "InvalidOffset: UTF-16 offset {} is out of range",
+                    offset
+                ))
+            })
+    }
+
+    /// Converts a byte offset, e.g. from a `ResolvedSpan`, into a UTF-16 offset.
+    pub fn byte_to_utf16(&self, file: String, offset: usize) -> Result<usize, JsValue> {
+        self.text_source(&file)?
+            .byte_to_utf16(offset)
+            .ok_or_else(|| {
+                JsValue::from_str(&format!(
+                    "InvalidOffset: byte offset {} is out of range or inside a character",
+                    offset
+                ))
+            })
+    }
+
+    /// Syntax errors of a file from parsing alone, without compiling.
+    pub fn check_syntax(&self, file: String) -> Result<Vec<js_types::Diagnostics>, JsValue> {
+        self.lint(file)
+    }
+
+    /// Syntax errors of a file as diagnostics. Reads the stored source, which `edit` keeps
+    /// reparsed, without going through the world, so packages and fonts are never touched.
+    pub fn lint(&self, file: String) -> Result<Vec<js_types::Diagnostics>, JsValue> {
+        let source = self.text_source(&file)?;
+
+        Ok(source
+            .root()
+            .errors()
+            .into_iter()
+            .map(|err| js_types::Diagnostics::from_syntax_error(err, &source))
+            .collect())
+    }
+
+    /// Formats a file with typstyle, `column_width` defaults to 120. Files with syntax errors
+    /// are refused.
+    pub fn format(&self, file: String, column_width: Option<usize>) -> Result<String, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        if let Some(message) = syntax_error_message(&file, &source) {
+            return Err(JsValue::from_str(&format!("SyntaxError: {}", message)));
+        }
+
+        format_source(&source, column_width)
+    }
+
+    /// `format` as minimal edits against the current text, which keeps the cursor in place.
+    /// Files with syntax errors are returned unchanged and marked as skipped.
+    pub fn format_edits(
+        &self,
+        file: String,
+        column_width: Option<usize>,
+    ) -> Result<js_types::FormatResult, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        if let Some(message) = syntax_error_message(&file, &source) {
+            return Ok(js_types::FormatResult {
+                text: source.text().to_string(),
+                edits: Vec::new(),
+                skipped: true,
+                message: Some(message),
+            });
+        }
+
+        let text = format_source(&source, column_width)?;
+        let edits = diff::diff(source.text(), &text)
+            .into_iter()
+            .map(|edit| js_types::TextEdit {
+                start: edit.range.start,
+                end: edit.range.end,
+                text: edit.text,
+            })
+            .collect();
+
+        Ok(js_types::FormatResult {
+            text,
+            edits,
+            skipped: false,
+            message: None,
+        })
+    }
+
+    pub fn semantic_tokens(&self, file: String) -> Result<Vec<js_types::SemanticToken>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        Ok(highlight::semantic_tokens(&source))
+    }
+
+    /// `semantic_tokens` with the result encoded as `format`, for large files.
+    pub fn semantic_tokens_encoded(
+        &self,
+        file: String,
+        format: js_types::OutputFormat,
+    ) -> Result<JsValue, JsValue> {
+        encoding::encode(&self.semantic_tokens(file)?, format)
+    }
+
+    pub fn folding_ranges(&self, file: String) -> Result<Vec<js_types::FoldingRange>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        Ok(folding::folding_ranges(&source))
+    }
+
+    pub fn equation_alt_text(&self) -> Result<Vec<js_types::EquationAltText>, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        Ok(accessibility::equation_alt_texts(self, &doc))
+    }
+
+    /// The prose of the last compiled document for spell checking, without code, math and
+    /// raw text. Runs are in reading order with the span of their markup, words hyphenated
+    /// at a line end are joined. `page` restricts the runs to one page.
+    pub fn extract_text(&self, page: Option<usize>) -> Result<Vec<js_types::TextRun>, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+        if let Some(page) = page.filter(|page| *page >= doc.pages.len()) {
+            return Err(JsValue::from_str(&format!(
+                "InvalidPage: page {} out of range, document has {} pages",
+                page,
+                doc.pages.len()
+            )));
+        }
+
+        Ok(prose::extract(&doc, page, &self.sources.read()))
+    }
+
+    pub fn stats(&self) -> Result<js_types::DocumentStats, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        Ok(stats::document_stats(&doc))
+    }
 
-        Ok(())
+    /// The fonts the last compiled document was laid out with, and the font families it
+    /// asked for that are not loaded, from the compile's warnings and the `text` set rules
+    /// of the project.
+    pub fn font_usage(&self) -> Result<js_types::FontUsage, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        let sources = self.sources.read();
+        let mut missing = self.missing_fonts.clone();
+        missing.extend(fonts::missing_from_sources(
+            sources
+                .iter()
+                .filter(|(id, entry)| id.package().is_none() && entry.is_utf8())
+                .map(|(_, entry)| &entry.source),
+            self.book(),
+        ));
+        missing.sort_by_key(|family| family.to_lowercase());
+        missing.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+        Ok(js_types::FontUsage {
+            used: fonts::used_fonts(&doc, &self.fonts.read()),
+            missing,
+        })
     }
 
-    pub fn move_file(&mut self, old: String, new: String) -> Result<(), JsValue> {
-        let old_id = FileId::new(None, VirtualPath::new(&old));
-        let new_id = FileId::new(None, VirtualPath::new(&new));
+    /// The fonts the last compiled document was laid out with and how many of their glyphs
+    /// are notdef boxes, to find out why text renders as tofu. Empty before the first compile.
+    pub fn fonts_used(&self) -> Vec<js_types::UsedFont> {
+        match self.last_doc.lock().as_ref() {
+            Some(doc) => fonts::used_fonts(doc, &self.fonts.read()),
+            None => Vec::new(),
+        }
+    }
 
-        let entry = self.sources.write().unwrap().remove(&old_id).unwrap();
-        self.sources.write().unwrap().insert(new_id, entry);
+    /// Files read by the last compile, including missing ones, sorted. Package files are
+    /// named like `@preview/cetz:0.3.1/src/lib.typ`.
+    pub fn last_dependencies(&self) -> Vec<String> {
+        let mut paths = self
+            .dependencies
+            .lock()
+            .iter()
+            .map(|id| {
+                paths::qualified_path(*id)
+                    .to_string_lossy()
+                    .trim_start_matches('/')
+                    .to_string()
+            })
+            .collect::<Vec<String>>();
+        paths.sort();
+        paths
+    }
 
-        Ok(())
+    /// Stable hash of the last compiled document as hex, equal for identical output. Hashes
+    /// the SVG of every page, frames hold spans which differ between runs. Pin the clock with
+    /// `set_now` when the document uses `datetime.today()`.
+    pub fn document_fingerprint(&self) -> Result<String, JsValue> {
+        let doc = self.last_doc.lock().clone();
+        let doc = doc.ok_or_else(|| JsValue::from_str("No compiled document, compile first"))?;
+
+        let pages = doc
+            .pages
+            .iter()
+            .map(typst_svg::svg)
+            .collect::<Vec<String>>();
+        Ok(format!("{:032x}", typst::utils::hash128(&pages)))
     }
 
-    pub fn imports(&self) -> Result<(), JsValue> {
-        let res = analyze_import(
-            self,
-            &LinkedNode::new(self.source(self.main()).unwrap().root()),
-        );
-        log(format!("imports: {:#?}", res).as_str());
-        Ok(())
+    /// Stable hash of all project files (without packages) as hex.
+    pub fn source_fingerprint(&self) -> String {
+        let sources = self.sources.read();
+        let mut files = sources
+            .iter()
+            .filter(|(id, _)| id.package().is_none() && !self.is_scratch(**id))
+            .map(|(id, entry)| (id.vpath().as_rooted_path(), entry.bytes()))
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.0.cmp(b.0));
+
+        format!("{:032x}", typst::utils::hash128(&files))
     }
 
     pub fn get_ast(&self, mut path: String) -> Result<js_types::AstNode, JsValue> {
@@ -549,98 +2612,184 @@ impl SuiteCore {
         Ok(js_types::AstNode::from_source(main_source))
     }
 
+    /// The smallest syntax node covering `offset`, `undefined` if the offset is out of bounds.
+    pub fn ast_at(
+        &self,
+        file: String,
+        offset: usize,
+    ) -> Result<Option<js_types::AstNode>, JsValue> {
+        let source = self
+            .source(FileId::new(None, VirtualPath::new(&file)))
+            .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        let root = LinkedNode::new(source.root());
+        Ok(root
+            .leaf_at(offset, typst::syntax::Side::After)
+            .map(js_types::AstNode::from_node))
+    }
+
     pub fn edit(
         &mut self,
         file: String,
         text: String,
         begin: usize,
         end: usize,
-    ) -> Result<(), JsValue> {
+    ) -> Result<js_types::TextRange, JsValue> {
         let id = FileId::new(None, VirtualPath::new(&file));
-        let mut binding = self.sources.write().unwrap();
+        let current = self
+            .sources
+            .read()
+            .get(&id)
+            .map_or(0, |entry| entry.source.text().len());
+        let size = (current + text.len()).saturating_sub(end.saturating_sub(begin));
+        self.check_file_limits(id, &file, size)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        let mut binding = self.sources.write();
         let entry = binding
             .get_mut(&id)
             .ok_or(JsValue::from_str("file not found"))?;
 
-        let range = entry.source.edit(begin..end, text.as_str());
+        let range = entry.edit(begin..end, text.as_str());
 
-        Ok(())
+        Ok(range.into())
+    }
+
+    /// Replaces the content of a file by applying the minimal edits between
+    /// the current and the new text, keeping spans of unchanged parts stable.
+    pub fn replace_with_diff(
+        &mut self,
+        file: String,
+        new_text: String,
+    ) -> Result<js_types::DiffResult, JsValue> {
+        let id = FileId::new(None, VirtualPath::new(&file));
+        self.check_file_limits(id, &file, new_text.len())
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let mut binding = self.sources.write();
+        let entry = binding
+            .get_mut(&id)
+            .ok_or(JsValue::from_str("file not found"))?;
+
+        let edits = diff::diff(entry.source.text(), &new_text);
+
+        // Apply back to front so the offsets of the remaining edits stay valid
+        let mut combined: Option<std::ops::Range<usize>> = None;
+        for edit in edits.iter().rev() {
+            let delta = edit.text.len() as isize - edit.range.len() as isize;
+            let reparsed = entry.edit(edit.range.clone(), &edit.text);
+
+            combined = Some(match combined {
+                Some(range) => {
+                    let start = if range.start >= edit.range.end {
+                        (range.start as isize + delta) as usize
+                    } else {
+                        range.start
+                    };
+                    let end = (range.end as isize + delta) as usize;
+                    reparsed.start.min(start)..reparsed.end.max(end)
+                }
+                None => reparsed,
+            });
+        }
+
+        Ok(js_types::DiffResult {
+            edits: edits
+                .into_iter()
+                .map(|edit| js_types::TextEdit {
+                    start: edit.range.start,
+                    end: edit.range.end,
+                    text: edit.text,
+                })
+                .collect(),
+            range: combined.map(js_types::TextRange::from),
+        })
+    }
+
+    /// Registers `spec` if automatic registration is on and its namespace can be fetched from.
+    fn auto_register(&self, spec: &PackageSpec) -> bool {
+        if !self.auto_register_packages
+            || (spec.namespace != package_index::PREVIEW_NAMESPACE
+                && registry::find(&self.registries, spec).is_none())
+        {
+            return false;
+        }
+
+        let mut lock = self.packages.write();
+        // Another file of the same package may have registered it already
+        if prefetch::find_package(&lock, spec).is_none() {
+            logging::log(LogLevel::Info, || {
+                format!("registering imported package {}", spec)
+            });
+            lock.push(spec.clone().into());
+            self.package_index_stale.store(true, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Rebuilds the package list used by autocompletion after packages were registered
+    /// automatically or pinned to a version by `prefetch_packages`.
+    fn refresh_package_index(&mut self) {
+        if self.package_index_stale.swap(false, Ordering::Relaxed) {
+            self.package_index = OnceLock::default();
+        }
     }
 
     fn get_file_entry(&self, id: FileId) -> FileResult<FileEntry> {
+        if self.compiling {
+            self.dependencies.lock().insert(id);
+        }
+
         // log(format!("accessing file entry: {:?}", id).as_str()); Debug
 
-        logWasm(
+        logging::log(LogLevel::Trace, || {
             format!(
                 "accessing file entry: {:?}, package: {:?}",
                 id,
                 id.package()
             )
-            .as_str(),
-        );
+        });
 
         match id.package() {
             Some(package) => {
-                let mut lock = self.packages.write().unwrap();
-                let int_package_opt: Option<&mut PackageWrapper> =
-                    lock.iter_mut().find(|p| package.compare(*p));
+                let lock = self.packages.read();
+                let Some(index) = prefetch::find_package(&lock, package) else {
+                    drop(lock);
+                    if self.auto_register(package) {
+                        return Err(FileError::Package(PackageError::NetworkFailed(Some(
+                            eco_format!(
+                                "package {} was registered automatically, call prefetch_packages() before compiling",
+                                package
+                            ),
+                        ))));
+                    }
+                    return Err(FileError::NotFound(
+                        id.vpath().as_rootless_path().to_path_buf(),
+                    ));
+                };
 
-                if int_package_opt.is_none() {
-                    return Err(typst::diag::FileError::NotFound(
+                // Packages are downloaded ahead of time by `prefetch_packages`, never while compiling
+                let sources = self.sources.read();
+                if let Some(entry) = sources.get(&id) {
+                    return Ok(entry.clone());
+                }
+                if sources.keys().any(|other| other.package() == Some(package)) {
+                    return Err(FileError::NotFound(
                         id.vpath().as_rootless_path().to_path_buf(),
                     ));
                 }
 
-                let int_package = int_package_opt.unwrap();
-
-                if int_package.fetched
-                    && !(int_package.namespace().starts_with("wolframe-")
-                        && int_package.version
-                            == ExtendedPackageVersion::from_str("latest").unwrap())
-                {
-                    logWasm(
-                        format!(
-                            "package already fetched: {:?}, {}, {}, {}",
-                            id,
-                            int_package.namespace().starts_with("wolframe-"),
-                            (int_package.version
-                                == ExtendedPackageVersion::from_str("latest").unwrap()),
-                            !(int_package.namespace().starts_with("wolframe-")
-                                && (int_package.version
-                                    == ExtendedPackageVersion::from_str("latest").unwrap()))
-                        )
-                        .as_str(),
-                    );
-                    let sources = self.sources.read().unwrap();
-                    Ok(sources.get(&id).unwrap().clone())
-                } else {
-                    logWasm(format!("fetching package: {:?}", id).as_str());
-                    /* let path = format!(
-                        "https://raw.githubusercontent.com/typst/packages/refs/heads/main/packages/preview/{}/{}/{}",
-                        id.package().unwrap().name,
-                        id.package().unwrap().version,
-                        id.vpath().as_rootless_path().to_str().unwrap()
-                    ); */
-                    let fetched_sources = package.fetch();
-                    {
-                        let mut writer = self.sources.write().unwrap();
-                        for (id, entry) in fetched_sources.iter() {
-                            writer.insert(*id, entry.clone());
-                        }
-                    }
-                    int_package.fetched = true;
-                    if fetched_sources.contains_key(&id) {
-                        Ok(fetched_sources.get(&id).unwrap().clone())
-                    } else {
-                        Err(typst::diag::FileError::NotSource)
-                    }
-                }
+                Err(FileError::Package(lock[index].error.clone().unwrap_or_else(|| {
+                    PackageError::NetworkFailed(Some(eco_format!(
+                        "package {} has not been downloaded, call prefetch_packages() before compiling",
+                        package
+                    )))
+                })))
             }
             None => {
-                let sources = self.sources.read().unwrap();
+                let sources = self.sources.read();
                 match sources.get(&id) {
                     Some(entry) => Ok(entry.clone()),
-                    None => Err(typst::diag::FileError::NotSource),
+                    None => Err(FileError::NotSource),
                 }
             }
         }
@@ -649,36 +2798,63 @@ impl SuiteCore {
 
 impl World for SuiteCore {
     fn library(&self) -> &LazyHash<Library> {
-        self.library
-            .get_or_init(|| LazyHash::new(Library::builder().build()))
+        if self.sized_compile {
+            return self
+                .sized_library
+                .get_or_init(|| self.build_library(self.page_size));
+        }
+        self.library.get_or_init(|| self.build_library(None))
     }
 
     fn book(&self) -> &LazyHash<FontBook> {
-        self.book.get_or_init(|| todo!())
+        // Derived from the fonts, so a reset book is rebuilt instead of panicking
+        self.book
+            .get_or_init(|| LazyHash::new(FontBook::from_fonts(self.fonts.read().iter())))
     }
 
     fn main(&self) -> FileId {
-        FileId::new(None, VirtualPath::new(&self.root))
+        self.entrypoint
+            .unwrap_or_else(|| FileId::new(None, VirtualPath::new(&self.root)))
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        self.get_file_entry(id).map(|entry| entry.source)
+        self.check_cancelled()?;
+        let source = self.get_file_entry(id).and_then(|entry| {
+            if entry.is_utf8() {
+                Ok(entry.source)
+            } else {
+                Err(FileError::InvalidUtf8)
+            }
+        });
+        // Only compiles report progress, not IDE features reading the main file
+        if source.is_ok() && self.compiling && id == self.main() {
+            self.report_progress(|p| p.parsed());
+        }
+        source
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.check_cancelled()?;
         self.get_file_entry(id).map(|entry| entry.bytes())
     }
 
     fn font(&self, index: usize) -> Option<Font> {
-        self.fonts.lock().unwrap().get(index).cloned()
+        self.fonts.read().get(index).cloned()
     }
 
     fn today(&self, offset: Option<i64>) -> Option<Datetime> {
-        let now = self.now.get_or_init(chrono::Local::now);
+        let now = self
+            .now
+            .get_or_init(|| self.clock.unwrap_or_else(|| Local::now().fixed_offset()));
 
+        // Both branches shift the same instant, only the timezone differs
         let naive = match offset {
-            None => now.naive_local(),
-            Some(o) => now.naive_utc() + chrono::Duration::hours(o),
+            None => now.date_naive(),
+            Some(hours) => {
+                let minutes = hours.checked_mul(60)?;
+                let offset = FixedOffset::east_opt(i32::try_from(minutes.checked_mul(60)?).ok()?)?;
+                now.with_timezone(&offset).date_naive()
+            }
         };
 
         Datetime::from_ymd(
@@ -691,38 +2867,928 @@ impl World for SuiteCore {
     // TODO: implement packages()
     fn packages(&self) -> &[(PackageSpec, Option<EcoString>)] {
         self.package_index.get_or_init(|| {
-            let lock = self.packages.read().unwrap();
+            let lock = self.packages.read();
+            let latest = self.latest_versions.read();
             lock.iter()
-                .map(|p| (p.clone().into(), p.description.clone()))
+                .map(|p| {
+                    let mut spec: PackageSpec = p.clone().into();
+                    // Offer a concrete version for latest wrappers when one is known
+                    if p.is_latest() {
+                        let key = (p.namespace.clone(), p.name.clone());
+                        if let Some(version) = p.resolved.or_else(|| latest.get(&key).copied()) {
+                            spec.version = version;
+                        }
+                    }
+                    (spec, p.description.clone())
+                })
                 .collect()
         })
     }
 }
 
 impl SuiteCore {
+    /// Compiles and renders the pages whose hash changed since the last compile, or all of
+    /// them unless `skip_unchanged`.
+    fn compile_pages(
+        &mut self,
+        opts: js_types::RenderOptions,
+        skip_unchanged: bool,
+    ) -> Result<js_types::ChangedPages, Vec<js_types::Diagnostics>> {
+        if !(opts.gap_pt.is_finite() && opts.gap_pt >= 0.0) {
+            return Err(vec![js_types::Diagnostics::error(format!(
+                "InvalidGap: gap_pt must be a non-negative number, got {}",
+                opts.gap_pt
+            ))]);
+        }
+        if self.target == js_types::Target::Html {
+            return Err(vec![html_unsupported()]);
+        }
+        if !self.has_main() {
+            return Err(vec![js_types::Diagnostics::error(
+                self.missing_main_message(),
+            )]);
+        }
+
+        if self.is_cancelled() {
+            return Err(vec![js_types::Diagnostics::cancelled()]);
+        }
+
+        self.now = OnceLock::default();
+        self.report_progress(|p| p.start());
+        let started = js_sys::Date::now();
+        let mut profile = profile::Profile::start(self.profiling.as_ref());
+
+        self.dependencies.lock().clear();
+        self.start_deadline();
+        self.compiling = true;
+        let warned = profile.measure(|| "compile".into(), || typst::compile(self));
+        self.compiling = false;
+        self.refresh_package_index();
+        self.missing_fonts = fonts::missing_from_warnings(&warned.warnings);
+        let output = warned.output;
+        // Strict compiles fail on warnings, they are reported after the errors
+        let promoted = if self.strict {
+            warned.warnings
+        } else {
+            Default::default()
+        };
+
+        // Errors of a cancelled compile are mostly files that failed to load on purpose
+        if self.is_cancelled() {
+            self.report_progress(|p| p.report("done", 0, 0));
+            return Err(vec![js_types::Diagnostics::cancelled()]);
+        }
+        if let Some(exceeded) = self.time_limit_exceeded() {
+            self.report_progress(|p| p.report("done", 0, 0));
+            return Err(vec![js_types::Diagnostics::error(exceeded.to_string())]);
+        }
+
+        match output {
+            Ok(doc) if promoted.is_empty() => {
+                self.remember_doc(&doc);
+                *self.last_doc.lock() = Some(doc.clone());
+                let total = doc.pages.len();
+                self.report_progress(|p| p.report("layout", total, total));
+
+                let doc = if opts.transparent {
+                    transparent_pages(doc)
+                } else {
+                    doc
+                };
+                let doc = Document {
+                    pages: doc
+                        .pages
+                        .iter()
+                        .map(|page| self.preview.apply(page))
+                        .collect(),
+                    ..doc
+                };
+
+                // Hashed after the render options are applied, so changing them counts
+                let hashes = doc
+                    .pages
+                    .iter()
+                    .map(|page| typst::utils::hash128(&(&page.frame, &page.fill)))
+                    .collect::<Vec<u128>>();
+                let changed = hashes
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, hash)| self.page_hashes.get(*i) != Some(hash))
+                    .map(|(i, _)| i)
+                    .collect::<Vec<usize>>();
+                let all_unchanged = changed.is_empty() && hashes.len() == self.page_hashes.len();
+
+                let sources = opts.annotate_spans.then(|| self.sources.read().clone());
+                let pages = if opts.merged {
+                    let svg = if skip_unchanged && all_unchanged {
+                        String::new()
+                    } else {
+                        profile.measure(
+                            || "svg merged".into(),
+                            || match &sources {
+                                Some(sources) => {
+                                    annotate::svg_merged(&doc, Abs::pt(opts.gap_pt), sources)
+                                }
+                                None => typst_svg::svg_merged(&doc, Abs::pt(opts.gap_pt)),
+                            },
+                        )
+                    };
+                    self.report_progress(|p| p.report("render", 1, 1));
+                    vec![svg]
+                } else {
+                    let mut pages = Vec::with_capacity(total);
+                    for (i, page) in doc.pages.iter().enumerate() {
+                        if self.is_cancelled() {
+                            self.report_progress(|p| p.report("done", 0, 0));
+                            return Err(vec![js_types::Diagnostics::cancelled()]);
+                        }
+                        if let Some(exceeded) = self.time_limit_exceeded() {
+                            self.report_progress(|p| p.report("done", 0, 0));
+                            return Err(vec![js_types::Diagnostics::error(exceeded.to_string())]);
+                        }
+                        let render = !skip_unchanged || changed.contains(&i);
+                        pages.push(if render {
+                            profile.measure(
+                                || format!("svg page {}", i + 1),
+                                || match &sources {
+                                    Some(sources) => annotate::svg(page, sources),
+                                    None => typst_svg::svg(page),
+                                },
+                            )
+                        } else {
+                            String::new()
+                        });
+                        self.report_progress(|p| p.report("render", i + 1, total));
+                    }
+                    pages
+                };
+                self.page_hashes = hashes;
+
+                self.report_progress(|p| p.report("done", total, total));
+                self.last_profile = profile.finish();
+                logging::emit(
+                    "compile_done",
+                    &logging::CompileDone {
+                        ms: js_sys::Date::now() - started,
+                        pages: total,
+                        errors: 0,
+                    },
+                );
+                Ok(js_types::ChangedPages { pages, changed })
+            }
+            output => {
+                let err = output.err().unwrap_or_default();
+                let errs = profile.measure(
+                    || "diagnostics".into(),
+                    || {
+                        let mut errs: Vec<js_types::Diagnostics> = Vec::new();
+
+                        for diag in err {
+                            errs.push(js_types::Diagnostics::from_diag(
+                                diag,
+                                self.sources.read().clone(),
+                                self.library(),
+                            ));
+                        }
+                        for diag in promoted {
+                            let mut warning = js_types::Diagnostics::from_diag(
+                                diag,
+                                self.sources.read().clone(),
+                                self.library(),
+                            );
+                            warning.promoted = true;
+                            errs.push(warning);
+                        }
+                        errs
+                    },
+                );
+
+                self.report_progress(|p| p.report("done", 0, 0));
+                self.last_profile = profile.finish();
+                logging::emit(
+                    "compile_done",
+                    &logging::CompileDone {
+                        ms: js_sys::Date::now() - started,
+                        pages: 0,
+                        errors: errs.len(),
+                    },
+                );
+                Err(errs)
+            }
+        }
+    }
+
+    /// Keeps `doc` as the document of the current main file and of every file it used.
+    fn remember_doc(&self, doc: &Document) {
+        let main = self.main();
+        let mut entry_docs = self.entry_docs.lock();
+        entry_docs.retain(|(entrypoint, _)| *entrypoint != main);
+        entry_docs.insert(0, (main, doc.clone()));
+        entry_docs.truncate(MAX_ENTRY_DOCS);
+
+        let mut doc_entrypoints = self.doc_entrypoints.lock();
+        for id in self.dependencies.lock().iter() {
+            doc_entrypoints.insert(*id, main);
+        }
+    }
+
+    /// The document of the entrypoint `id` was last compiled under, the last document if
+    /// that one is no longer cached.
+    fn document_for(&self, id: FileId) -> Option<Document> {
+        let entrypoint = self.doc_entrypoints.lock().get(&id).copied();
+        entrypoint
+            .and_then(|entrypoint| {
+                self.entry_docs
+                    .lock()
+                    .iter()
+                    .find(|(other, _)| *other == entrypoint)
+                    .map(|(_, doc)| doc.clone())
+            })
+            .or_else(|| self.last_doc.lock().clone())
+    }
+
+    /// The source of a project text file, read without going through the world.
+    fn text_source(&self, file: &str) -> Result<Source, JsValue> {
+        let id = FileId::new(None, VirtualPath::new(file));
+        let sources = self.sources.read();
+        let entry = sources
+            .get(&id)
+            .ok_or_else(|| JsValue::from_str(&format!("NotFound: file '{}'", file)))?;
+        if !entry.is_utf8() {
+            return Err(JsValue::from_str(&format!(
+                "InvalidUtf8: '{}' is a binary file",
+                file
+            )));
+        }
+        Ok(entry.source.clone())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    /// Fails file loads of a running compile once it got cancelled or ran out of time.
+    fn check_cancelled(&self) -> FileResult<()> {
+        if self.compiling && self.is_cancelled() {
+            return Err(FileError::Other(Some("compilation was cancelled".into())));
+        }
+        if let Some(exceeded) = self.time_limit_exceeded().filter(|_| self.compiling) {
+            return Err(FileError::Other(Some(eco_format!("{}", exceeded))));
+        }
+        Ok(())
+    }
+
+    /// Starts the clock for `max_compile_ms`, the clock is only read when the limit is set.
+    fn start_deadline(&mut self) {
+        self.deadline = self
+            .limits
+            .max_compile_ms
+            .map(|max| (js_sys::Date::now(), max));
+    }
+
+    /// typst can't be interrupted while evaluating or laying out, so the limit is checked
+    /// whenever a file is loaded and between rendered pages.
+    fn time_limit_exceeded(&self) -> Option<limits::LimitExceeded> {
+        let (started, max) = self.deadline?;
+        let elapsed = (js_sys::Date::now() - started) as usize;
+        limits::check(
+            "max_compile_ms",
+            Some(max),
+            elapsed,
+            self.main().vpath().as_rooted_path().display(),
+        )
+        .err()
+    }
+
+    fn report_progress(&self, f: impl FnOnce(&progress::ProgressCallback)) {
+        if let Some(progress) = &self.progress {
+            f(progress);
+        }
+    }
+
+    fn missing_main_message(&self) -> String {
+        let mut files = self
+            .sources
+            .read()
+            .keys()
+            .filter(|id| id.package().is_none())
+            .map(|id| id.vpath().as_rooted_path().display().to_string())
+            .collect::<Vec<String>>();
+        files.sort();
+
+        format!(
+            "main file '{}' does not exist, available files: {}",
+            self.main().vpath().as_rooted_path().display(),
+            if files.is_empty() {
+                "none".to_string()
+            } else {
+                files.join(", ")
+            }
+        )
+    }
+
     fn reset(&mut self) {
-        self.library = OnceLock::default();
-        self.sources = Arc::new(RwLock::new(HashMap::new()));
+        self.reset_library();
         self.now = OnceLock::default();
     }
 
-    fn start_embedded_fonts() -> (FontBook, Vec<Font>) {
-        let mut book = FontBook::new();
-        let mut fonts = Vec::new();
+    fn reset_library(&mut self) {
+        self.library = OnceLock::default();
+        self.sized_library = OnceLock::default();
+        self.library_symbols.get_mut().clear();
+    }
+
+    /// The standard library with the configured inputs, and a page size as if the document
+    /// started with `#set page(width: .., height: ..)`.
+    fn build_library(&self, page_size: Option<(f64, f64)>) -> LazyHash<Library> {
+        let mut library = Library::builder()
+            .with_inputs(
+                self.inputs
+                    .iter()
+                    .map(|(key, value)| (key.as_str().into(), Value::Str(value.as_str().into())))
+                    .collect::<Dict>(),
+            )
+            .build();
+        if let Some((width, height)) = page_size {
+            library
+                .styles
+                .set(PageElem::set_width(Smart::Custom(Abs::pt(width).into())));
+            library
+                .styles
+                .set(PageElem::set_height(Smart::Custom(Abs::pt(height).into())));
+        }
+        LazyHash::new(library)
+    }
+
+    /// Checks a project file of `size` bytes replacing whatever is stored under `id`.
+    fn check_file_limits(
+        &self,
+        id: FileId,
+        path: &str,
+        size: usize,
+    ) -> Result<(), limits::LimitExceeded> {
+        limits::check("max_file_size", self.limits.max_file_size, size, path)?;
+
+        let sources = self.sources.read();
+        let others = sources
+            .iter()
+            .filter(|(other, _)| {
+                **other != id && other.package().is_none() && !self.is_scratch(**other)
+            })
+            .map(|(_, entry)| entry.bytes().len())
+            .sum::<usize>();
+        limits::check(
+            "max_project_size",
+            self.limits.max_project_size,
+            others + size,
+            path,
+        )
+    }
+
+    /// Checks the files of a snapshot against the limits `add_file` and package extraction
+    /// apply, package files are counted per package like the entries of its archive.
+    fn check_state_limits(&self, files: &[state::StateFile]) -> Result<(), limits::LimitExceeded> {
+        let mut project_size = 0;
+        let mut archives = HashMap::<PackageSpec, (usize, usize)>::new();
+        for file in files {
+            let id = paths::parse_qualified_path(&file.path);
+            let size = file.data.len();
+            limits::check("max_file_size", self.limits.max_file_size, size, &file.path)?;
+
+            let Some(spec) = id.package() else {
+                project_size += size;
+                limits::check(
+                    "max_project_size",
+                    self.limits.max_project_size,
+                    project_size,
+                    &file.path,
+                )?;
+                continue;
+            };
+            let (entries, archive_size) = archives.entry(spec.clone()).or_default();
+            *entries += 1;
+            *archive_size += size;
+            limits::check(
+                "max_archive_entries",
+                self.limits.max_archive_entries,
+                *entries,
+                spec,
+            )?;
+            limits::check(
+                "max_archive_size",
+                self.limits.max_archive_size,
+                *archive_size,
+                spec,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Edits rewriting every project path reference affected by moving the files `moves` maps
+    /// from old to new ids, sorted by file and offset.
+    fn reference_edits(
+        &self,
+        sources: &HashMap<FileId, FileEntry>,
+        moves: &HashMap<FileId, FileId>,
+    ) -> Vec<js_types::FileTextEdit> {
+        let mut edits = Vec::new();
+        for (id, entry) in sources.iter() {
+            if id.package().is_some() || self.is_scratch(*id) {
+                continue;
+            }
+            let target_id = moves.get(id).copied().unwrap_or(*id);
+
+            for reference in paths::path_references(&entry.source) {
+                let resolved = reference.resolve(*id);
+                let moved_to = moves.get(&FileId::new(None, resolved.clone()));
+                // Relative paths inside a moved file now start from its new directory
+                let moved_relative = moves.contains_key(id) && !reference.is_rooted();
+                if moved_to.is_none() && !moved_relative {
+                    continue;
+                }
+
+                let dest = moved_to.map_or(resolved, |moved| moved.vpath().clone());
+                let path = if reference.is_rooted() {
+                    dest.as_rooted_path().to_string_lossy().into_owned()
+                } else {
+                    paths::relative_path(target_id.vpath(), &dest)
+                };
+                if path == reference.path {
+                    continue;
+                }
 
-        for data in typst_assets::fonts() {
-            let buffer = Bytes::from_static(data);
-            for font in Font::iter(buffer) {
-                book.push(font.info().clone());
-                fonts.push(font);
+                edits.push(js_types::FileTextEdit {
+                    file_path: target_id
+                        .vpath()
+                        .as_rooted_path()
+                        .to_string_lossy()
+                        .into_owned(),
+                    start_offset: reference.start,
+                    end_offset: reference.end,
+                    insert_text: paths::string_literal(&path),
+                });
             }
         }
 
-        (book, fonts)
+        edits.sort_by(|a, b| {
+            a.file_path
+                .cmp(&b.file_path)
+                .then(a.start_offset.cmp(&b.start_offset))
+        });
+        edits
+    }
+
+    fn import_edges(&self) -> graph::Edges {
+        graph::import_edges(
+            self.sources
+                .read()
+                .iter()
+                .filter(|(id, _)| !self.is_scratch(**id)),
+        )
+    }
+
+    fn is_scratch(&self, id: FileId) -> bool {
+        id.package().is_none()
+            && id
+                .vpath()
+                .as_rooted_path()
+                .starts_with(&self.scratch_prefix)
+    }
+
+    fn start_embedded_fonts() -> Vec<Font> {
+        typst_assets::fonts()
+            .flat_map(|data| Font::iter(Bytes::from_static(data)))
+            .collect()
+    }
+}
+
+/// The error of compiling with the `Html` target, which typst 0.12 does not have.
+fn html_unsupported() -> js_types::Diagnostics {
+    js_types::Diagnostics::error(
+        "Unsupported: HTML export requires typst 0.13 or newer, this build uses typst 0.12"
+            .to_string(),
+    )
+}
+
+/// Drops the default white background of pages without an explicit fill.
+fn transparent_pages(mut doc: Document) -> Document {
+    for page in &mut doc.pages {
+        if page.fill.is_auto() {
+            page.fill = Smart::Custom(None);
+        }
     }
+    doc
+}
+
+/// Why a source cannot be formatted, `None` if it has no syntax errors.
+fn syntax_error_message(file: &str, source: &Source) -> Option<String> {
+    let errors = source.root().errors();
+    let first = errors.first()?;
+    let line = source
+        .range(first.span)
+        .and_then(|range| source.byte_to_line(range.start))
+        .map_or(0, |line| line + 1);
+    Some(format!(
+        "cannot format '{}' with {} syntax error(s), first on line {}: {}",
+        file,
+        errors.len(),
+        line,
+        first.message
+    ))
+}
+
+fn format_source(source: &Source, column_width: Option<usize>) -> Result<String, JsValue> {
+    Typstyle::new(Config::new().with_width(column_width.unwrap_or(FORMAT_WIDTH)))
+        .format_source(source)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
 #[wasm_bindgen]
 pub fn greet(name: &str) -> String {
     format!("Hello, {}!", name)
 }
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn spec() -> PackageSpec {
+        PackageSpec::from_str("@preview/example:0.1.0").unwrap()
+    }
+
+    fn no_limits() -> js_types::ResourceLimits {
+        js_types::ResourceLimits {
+            max_file_size: None,
+            max_project_size: None,
+            max_archive_entries: None,
+            max_archive_size: None,
+            max_compile_ms: None,
+        }
+    }
+
+    /// A tar.gz with the entry names written as is, `tar::Builder` would normalize them.
+    fn archive(entries: &[(&str, tar::EntryType, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, entry_type, data) in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            if entry_type.is_file() {
+                header.set_size(data.len() as u64);
+            } else {
+                header.set_size(0);
+                header
+                    .set_link_name(std::str::from_utf8(data).unwrap())
+                    .unwrap();
+            }
+            header.set_cksum();
+            let content: &[u8] = if entry_type.is_file() { data } else { &[] };
+            builder.append(&header, content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn files(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        archive(
+            &entries
+                .iter()
+                .map(|(name, data)| (*name, tar::EntryType::Regular, *data))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn limit_error(result: PackageResult<HashMap<FileId, FileEntry>>) -> String {
+        match result {
+            Err(PackageError::Other(Some(message))) => message.to_string(),
+            Err(other) => panic!("expected a limit error, got {:?}", other),
+            Ok(_) => panic!("expected a limit error, the archive was accepted"),
+        }
+    }
+
+    #[test]
+    fn archive_entries_limit() {
+        let limits = js_types::ResourceLimits {
+            max_archive_entries: Some(2),
+            ..no_limits()
+        };
+
+        let at_limit = files(&[("a.typ", b"a"), ("b.typ", b"b")]);
+        assert_eq!(spec().extract(at_limit, &limits).unwrap().len(), 2);
+
+        let over = files(&[("a.typ", b"a"), ("b.typ", b"b"), ("c.typ", b"c")]);
+        assert!(limit_error(spec().extract(over, &limits))
+            .starts_with("LimitExceeded: max_archive_entries"));
+    }
+
+    #[test]
+    fn archive_size_limit() {
+        let limits = js_types::ResourceLimits {
+            max_archive_size: Some(10),
+            ..no_limits()
+        };
+
+        let at_limit = files(&[("a.typ", b"12345"), ("b.typ", b"12345")]);
+        assert!(spec().extract(at_limit, &limits).is_ok());
+
+        let over = files(&[("a.typ", b"12345"), ("b.typ", b"123456")]);
+        assert!(limit_error(spec().extract(over, &limits))
+            .starts_with("LimitExceeded: max_archive_size"));
+    }
+
+    #[test]
+    fn archive_file_size_limit() {
+        let limits = js_types::ResourceLimits {
+            max_file_size: Some(4),
+            ..no_limits()
+        };
+
+        assert!(spec()
+            .extract(files(&[("a.typ", b"1234")]), &limits)
+            .is_ok());
+
+        let over = files(&[("a.typ", b"1234"), ("big.typ", b"12345")]);
+        let message = limit_error(spec().extract(over, &limits));
+        assert!(message.starts_with("LimitExceeded: max_file_size"));
+        assert!(message.contains("big.typ"));
+    }
+
+    #[test]
+    fn project_size_limit_keeps_files() {
+        let mut core = SuiteCore::new("main.typ".into());
+        core.set_resource_limits(js_types::ResourceLimits {
+            max_project_size: Some(10),
+            ..no_limits()
+        });
+
+        let main = FileId::new(None, VirtualPath::new("main.typ"));
+        assert!(core.check_file_limits(main, "main.typ", 6).is_ok());
+        core.sources
+            .write()
+            .insert(main, FileEntry::new(main, "123456".into()));
+
+        let other = FileId::new(None, VirtualPath::new("other.typ"));
+        let exceeded = core.check_file_limits(other, "other.typ", 5).unwrap_err();
+        assert_eq!(exceeded.limit, "max_project_size");
+        assert_eq!(exceeded.actual, 11);
+        // Replacing a file only counts its new size
+        assert!(core.check_file_limits(main, "main.typ", 10).is_ok());
+        assert_eq!(core.sources.read().len(), 1);
+    }
+
+    #[test]
+    fn archive_paths_and_links() {
+        use tar::EntryType::{Link, Regular, Symlink};
+
+        let data = archive(&[
+            ("./typst.toml", Regular, b"toml"),
+            ("./src//lib.typ", Regular, b"lib"),
+            ("src/./utils.typ", Regular, b"utils"),
+            // Resolved relative to the link's directory, `alias.typ` only in a second pass
+            ("src/alias.typ", Symlink, b"../lib.typ"),
+            ("lib.typ", Symlink, b"src/lib.typ"),
+            // Hard links name their target from the archive root
+            ("./hard.typ", Link, b"./src//utils.typ"),
+            // Absolute, dangling and cyclic links are skipped
+            ("escape.typ", Symlink, b"/etc/passwd"),
+            ("dangling.typ", Symlink, b"missing.typ"),
+            ("a.typ", Symlink, b"b.typ"),
+            ("b.typ", Symlink, b"a.typ"),
+        ]);
+
+        let sources = spec().extract(data, &no_limits()).unwrap();
+        let mut files = sources
+            .iter()
+            .map(|(id, entry)| {
+                assert_eq!(id.package(), Some(&spec()));
+                let path = id.vpath().as_rooted_path().to_string_lossy().into_owned();
+                (path, String::from_utf8(entry.bytes().to_vec()).unwrap())
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let expected = [
+            ("/hard.typ", "utils"),
+            ("/lib.typ", "lib"),
+            ("/src/alias.typ", "lib"),
+            ("/src/lib.typ", "lib"),
+            ("/src/utils.typ", "utils"),
+            ("/typst.toml", "toml"),
+        ];
+        assert_eq!(
+            files,
+            expected.map(|(path, text)| (path.to_string(), text.to_string()))
+        );
+    }
+
+    #[test]
+    fn bom_is_reported_and_kept_for_data() {
+        let mut core = SuiteCore::new("main.typ".into());
+        core.add_file("data.csv".into(), "\u{feff}a,b\r\n1,2\r\n".into())
+            .unwrap();
+        core.add_file("main.typ".into(), "= Title".into()).unwrap();
+
+        assert!(core.has_bom("data.csv".into()).unwrap());
+        assert!(!core.has_bom("main.typ".into()).unwrap());
+        assert_eq!(core.get_file("data.csv".into()).unwrap(), "a,b\r\n1,2\r\n");
+
+        let id = FileId::new(None, VirtualPath::new("data.csv"));
+        let bytes = core.sources.read()[&id].bytes();
+        assert_eq!(bytes.as_slice(), "\u{feff}a,b\r\n1,2\r\n".as_bytes());
+    }
+
+    /// `(file, old text, new text)` of reference edits.
+    fn edit_texts(edits: &[js_types::FileTextEdit]) -> Vec<(String, String)> {
+        edits
+            .iter()
+            .map(|edit| (edit.file_path.clone(), edit.insert_text.clone()))
+            .collect()
+    }
+
+    fn text(core: &SuiteCore, path: &str) -> String {
+        core.get_file(path.into()).unwrap()
+    }
+
+    #[test]
+    fn move_directory_rewrites_references() {
+        let mut core = SuiteCore::new("main.typ".into());
+        core.add_file(
+            "main.typ".into(),
+            "#include \"chapters/intro.typ\"\n#include \"/chapters/outro.typ\"".into(),
+        )
+        .unwrap();
+        core.add_file(
+            "chapters/intro.typ".into(),
+            "#image(\"img/logo.png\")\n#include \"../common.typ\"\n#include \"/common.typ\"".into(),
+        )
+        .unwrap();
+        core.add_file("chapters/outro.typ".into(), "Bye".into())
+            .unwrap();
+        core.add_file("common.typ".into(), "Common".into()).unwrap();
+        let logo = FileId::new(None, VirtualPath::new("chapters/img/logo.png"));
+        core.sources
+            .write()
+            .insert(logo, FileEntry::from_bytes(logo, vec![0x89, b'P', 0xff]));
+
+        let dry = core
+            .move_directory("chapters".into(), "parts/chapters".into(), true, true)
+            .unwrap();
+        let expected = [
+            ("/main.typ", "\"parts/chapters/intro.typ\""),
+            ("/main.typ", "\"/parts/chapters/outro.typ\""),
+            ("/parts/chapters/intro.typ", "\"../../common.typ\""),
+        ]
+        .map(|(file, text)| (file.to_string(), text.to_string()));
+        assert_eq!(edit_texts(&dry), expected);
+        // A dry run changes nothing
+        assert!(text(&core, "chapters/intro.typ").contains("\"../common.typ\""));
+        assert!(text(&core, "main.typ").contains("\"chapters/intro.typ\""));
+
+        let edits = core
+            .move_directory("chapters".into(), "parts/chapters".into(), true, false)
+            .unwrap();
+        assert_eq!(edit_texts(&edits), expected);
+        assert_eq!(
+            text(&core, "main.typ"),
+            "#include \"parts/chapters/intro.typ\"\n#include \"/parts/chapters/outro.typ\""
+        );
+        assert_eq!(
+            text(&core, "parts/chapters/intro.typ"),
+            "#image(\"img/logo.png\")\n#include \"../../common.typ\"\n#include \"/common.typ\""
+        );
+
+        let sources = core.sources.read();
+        assert!(!sources.contains_key(&logo));
+        let logo = &sources[&FileId::new(None, VirtualPath::new("parts/chapters/img/logo.png"))];
+        assert!(!logo.is_utf8());
+        assert_eq!(logo.bytes().as_slice(), [0x89, b'P', 0xff]);
+    }
+
+    #[test]
+    fn move_file_keeps_bom_and_fresh_bytes() {
+        let mut core = SuiteCore::new("main.typ".into());
+        core.add_file("main.typ".into(), "#csv(\"data.csv\")".into())
+            .unwrap();
+        core.add_file("data.csv".into(), "\u{feff}a,b".into())
+            .unwrap();
+        let main = FileId::new(None, VirtualPath::new("main.typ"));
+        // Fill the byte cache the edit has to invalidate
+        core.sources.read()[&main].bytes();
+
+        core.move_file("data.csv".into(), "data/table.csv".into(), true, false)
+            .unwrap();
+        assert!(core.has_bom("data/table.csv".into()).unwrap());
+        assert_eq!(
+            core.sources.read()[&main].bytes().as_slice(),
+            b"#csv(\"data/table.csv\")"
+        );
+    }
+
+    #[test]
+    fn html_target_is_unsupported() {
+        let mut core = SuiteCore::new("main.typ".into());
+        core.add_file("main.typ".into(), "= Title".into()).unwrap();
+
+        let errors = core.compile_html().unwrap_err();
+        assert!(errors[0].message.starts_with("InvalidTarget"));
+
+        core.set_target(js_types::Target::Html);
+        for errors in [
+            core.compile_html().unwrap_err(),
+            core.compile(true).unwrap_err(),
+        ] {
+            assert_eq!(errors.len(), 1);
+            assert!(matches!(errors[0].severity, js_types::Severity::Error));
+            assert!(errors[0].message.starts_with("Unsupported: HTML export"));
+        }
+    }
+
+    #[test]
+    fn state_limits() {
+        let file = |path: &str, size: usize| state::StateFile {
+            path: path.to_string(),
+            data: vec![b'a'; size],
+        };
+        let files = [
+            file("/main.typ", 4),
+            file("/logo.png", 4),
+            file("/@preview/example:0.1.0/lib.typ", 3),
+            file("/@preview/example:0.1.0/typst.toml", 3),
+            file("/@preview/other:0.1.0/lib.typ", 3),
+        ];
+        let limit = |limits: js_types::ResourceLimits| {
+            let mut core = SuiteCore::new("main.typ".into());
+            core.set_resource_limits(limits);
+            core.check_state_limits(&files).err().map(|e| e.to_string())
+        };
+
+        let fitting = js_types::ResourceLimits {
+            max_file_size: Some(4),
+            max_project_size: Some(8),
+            max_archive_entries: Some(2),
+            max_archive_size: Some(6),
+            max_compile_ms: None,
+        };
+        assert_eq!(limit(fitting.clone()), None);
+        for (limits, expected) in [
+            (
+                js_types::ResourceLimits {
+                    max_file_size: Some(3),
+                    ..fitting.clone()
+                },
+                "LimitExceeded: max_file_size for '/main.typ' (4 > 3)",
+            ),
+            (
+                js_types::ResourceLimits {
+                    max_project_size: Some(7),
+                    ..fitting.clone()
+                },
+                "LimitExceeded: max_project_size for '/logo.png' (8 > 7)",
+            ),
+            (
+                js_types::ResourceLimits {
+                    max_archive_entries: Some(1),
+                    ..fitting.clone()
+                },
+                "LimitExceeded: max_archive_entries for '@preview/example:0.1.0' (2 > 1)",
+            ),
+            (
+                js_types::ResourceLimits {
+                    max_archive_size: Some(5),
+                    ..fitting.clone()
+                },
+                "LimitExceeded: max_archive_size for '@preview/example:0.1.0' (6 > 5)",
+            ),
+        ] {
+            assert_eq!(limit(limits).as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn binary_files_count_toward_sizes() {
+        let mut core = SuiteCore::new("main.typ".into());
+        core.set_resource_limits(js_types::ResourceLimits {
+            max_project_size: Some(10),
+            ..no_limits()
+        });
+        let logo = FileId::new(None, VirtualPath::new("logo.png"));
+        let font = FileId::new(Some(spec()), VirtualPath::new("fonts/font.otf"));
+        {
+            let mut sources = core.sources.write();
+            sources.insert(logo, FileEntry::from_bytes(logo, vec![0xff; 8]));
+            sources.insert(font, FileEntry::from_bytes(font, vec![0xff; 5]));
+        }
+
+        let usage = core.resource_usage();
+        assert_eq!((usage.project_size, usage.largest_file), (8, 8));
+        assert_eq!(usage.package_size, 5);
+
+        let main = FileId::new(None, VirtualPath::new("main.typ"));
+        assert!(core.check_file_limits(main, "main.typ", 2).is_ok());
+        assert!(core.check_file_limits(main, "main.typ", 3).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Default for `max_file_size`, far above any hand written source but small enough that a
+/// pasted blob does not stall reparsing.
+pub const DEFAULT_MAX_FILE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Default for `max_archive_size`, protects against decompression bombs from registries.
+pub const DEFAULT_MAX_ARCHIVE_SIZE: usize = 64 * 1024 * 1024;
+
+/// A violated resource limit, rendered as `LimitExceeded: <limit> ...` so hosts can match on it.
+pub struct LimitExceeded {
+    pub limit: &'static str,
+    pub actual: usize,
+    pub max: usize,
+    pub subject: String, // The file or package that hit the limit
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LimitExceeded: {} for '{}' ({} > {})",
+            self.limit, self.subject, self.actual, self.max
+        )
+    }
+}
+
+/// Fails if `actual` exceeds the optional `max`.
+pub fn check(
+    limit: &'static str,
+    max: Option<usize>,
+    actual: usize,
+    subject: impl ToString,
+) -> Result<(), LimitExceeded> {
+    match max {
+        Some(max) if actual > max => Err(LimitExceeded {
+            limit,
+            actual,
+            max,
+            subject: subject.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
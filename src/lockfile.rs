@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use typst::{foundations::Bytes, syntax::FileId};
+
+use crate::file_entry::FileEntry;
+
+/// Version of the lockfile format, bumped on incompatible changes.
+pub const LOCKFILE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,  // The version as it was registered (may be "latest")
+    pub resolved: String, // The concrete version that got fetched
+    pub hash: Option<String>,
+}
+
+impl Lockfile {
+    pub fn new(packages: Vec<LockedPackage>) -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            packages,
+        }
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, String> {
+        let lockfile: Self = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        if lockfile.version != LOCKFILE_VERSION {
+            return Err(format!(
+                "unsupported lockfile version {} (expected {})",
+                lockfile.version, LOCKFILE_VERSION
+            ));
+        }
+        Ok(lockfile)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+}
+
+/// Hashes the extracted files of a package independent of their order in the archive.
+pub fn content_hash(files: &HashMap<FileId, FileEntry>) -> String {
+    let mut entries = files
+        .iter()
+        .map(|(id, entry)| {
+            (
+                id.vpath().as_rooted_path().to_string_lossy().into_owned(),
+                entry.bytes(),
+            )
+        })
+        .collect::<Vec<(String, Bytes)>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    format!("{:032x}", typst::utils::hash128(&entries))
+}
@@ -0,0 +1,101 @@
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use serde::Serialize;
+
+use crate::{emitEvent, errorWasm, logWasm, warn};
+
+/// Verbosity of the internal logs, every level includes the ones before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(format!(
+                "InvalidLogLevel: '{}', expected off, error, warn, info, debug or trace",
+                level
+            )),
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Warn as u8);
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Passes a message to the host if `level` is enabled, the message is only built then.
+/// Errors go to `errorWasm`, warnings to `console.warn` and everything else to `logWasm`.
+pub fn log(level: LogLevel, message: impl FnOnce() -> String) {
+    if !enabled(level) {
+        return;
+    }
+    let message = message();
+    match level {
+        LogLevel::Off => {}
+        LogLevel::Error => errorWasm(&message),
+        LogLevel::Warn => warn(&message),
+        LogLevel::Info | LogLevel::Debug | LogLevel::Trace => logWasm(&message),
+    }
+}
+
+/// Sends a machine-readable event to `emitEvent`, regardless of the log level.
+pub fn emit(kind: &str, payload: &impl Serialize) {
+    if let Ok(payload) = serde_wasm_bindgen::to_value(payload) {
+        emitEvent(kind, payload);
+    }
+}
+
+/*
+ * Events
+ */
+
+#[derive(Serialize)]
+pub struct PackageFetchStart<'a> {
+    pub package: &'a str,
+    pub url: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct PackageFetchDone<'a> {
+    pub package: &'a str,
+    pub ms: f64,
+    pub bytes: usize,
+}
+
+#[derive(Serialize)]
+pub struct PackageFetchFailed<'a> {
+    pub package: &'a str,
+    pub ms: f64,
+    pub error: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct CompileDone {
+    pub ms: f64,
+    pub pages: usize,  // Zero if the compile failed
+    pub errors: usize, // Number of error diagnostics
+}
@@ -0,0 +1,53 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::Deserialize;
+use typst::syntax::package::PackageVersion;
+
+/// Namespace of the packages listed in the official index.
+pub const PREVIEW_NAMESPACE: &str = "preview";
+
+pub const PREVIEW_INDEX_URL: &str = "https://packages.typst.org/preview/index.json";
+
+#[derive(Deserialize)]
+struct RawIndexEntry {
+    name: String,
+    version: String,
+    description: Option<String>,
+}
+
+pub struct IndexEntry {
+    pub name: String,
+    pub version: PackageVersion,
+    pub description: Option<String>,
+}
+
+/// Parses an `index.json` as served by packages.typst.org, keeping only the newest
+/// version of every package. Entries with invalid versions are skipped.
+pub fn parse_index(json: &[u8]) -> Result<Vec<IndexEntry>, String> {
+    let raw: Vec<RawIndexEntry> = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+
+    let mut newest: HashMap<String, IndexEntry> = HashMap::new();
+    for entry in raw {
+        let Ok(version) = PackageVersion::from_str(&entry.version) else {
+            continue;
+        };
+        if newest
+            .get(&entry.name)
+            .is_some_and(|known| known.version >= version)
+        {
+            continue;
+        }
+        newest.insert(
+            entry.name.clone(),
+            IndexEntry {
+                name: entry.name,
+                version,
+                description: entry.description,
+            },
+        );
+    }
+
+    let mut entries = newest.into_values().collect::<Vec<IndexEntry>>();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
@@ -0,0 +1,390 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use typst::syntax::{
+    ast::{self, AstNode},
+    package::PackageSpec,
+    FileId, LinkedNode, Side, Source, SyntaxKind, VirtualPath,
+};
+
+use crate::{
+    file_entry::FileEntry,
+    js_types::{
+        Completion, CompletionDetail, CompletionKind, DirEntry, ImportInfo, RawPackageSpec,
+        ResolvedSpan,
+    },
+};
+
+/// Functions whose first positional argument is a path into the project.
+const PATH_FUNCTIONS: &[&str] = &[
+    "image",
+    "read",
+    "json",
+    "csv",
+    "yaml",
+    "toml",
+    "xml",
+    "cbor",
+    "bibliography",
+];
+
+/// A string literal in a source that refers to another project file.
+pub struct PathReference {
+    pub start: usize, // Byte range of the literal, including the quotes
+    pub end: usize,
+    pub path: String, // The path as written
+}
+
+impl PathReference {
+    pub fn is_rooted(&self) -> bool {
+        self.path.starts_with('/')
+    }
+
+    /// The project path this reference resolves to when written in `id`.
+    pub fn resolve(&self, id: FileId) -> VirtualPath {
+        id.vpath().join(&self.path)
+    }
+}
+
+fn string_reference(node: &LinkedNode) -> Option<PathReference> {
+    let path = node.cast::<ast::Str>()?.get();
+    // Package imports like "@preview/name:0.1.0" don't point into the project
+    if path.starts_with('@') {
+        return None;
+    }
+
+    Some(PathReference {
+        start: node.offset(),
+        end: node.range().end,
+        path: path.to_string(),
+    })
+}
+
+/// The string literal holding the path of an import, include or file loading call.
+fn path_literal<'a>(node: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
+    match node.kind() {
+        SyntaxKind::ModuleImport | SyntaxKind::ModuleInclude => node
+            .children()
+            .find(|child| child.kind() == SyntaxKind::Str),
+        SyntaxKind::FuncCall => {
+            let callee = node.children().next();
+            if callee.is_some_and(|c| {
+                c.kind() == SyntaxKind::Ident && PATH_FUNCTIONS.contains(&c.text().as_str())
+            }) {
+                node.children()
+                    .find(|child| child.kind() == SyntaxKind::Args)
+                    .and_then(|args| args.children().find(|arg| arg.kind() == SyntaxKind::Str))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn collect(node: &LinkedNode, modules_only: bool, refs: &mut Vec<PathReference>) {
+    let target =
+        path_literal(node).filter(|_| !modules_only || node.kind() != SyntaxKind::FuncCall);
+    if let Some(reference) = target.as_ref().and_then(string_reference) {
+        refs.push(reference);
+    }
+
+    for child in node.children() {
+        collect(&child, modules_only, refs);
+    }
+}
+
+/// All import, include and file loading paths in a source, in document order.
+pub fn path_references(source: &Source) -> Vec<PathReference> {
+    let mut refs = Vec::new();
+    collect(&LinkedNode::new(source.root()), false, &mut refs);
+    refs
+}
+
+/// The import and include paths of a source, without the files it loads as data.
+pub fn module_references(source: &Source) -> Vec<PathReference> {
+    let mut refs = Vec::new();
+    collect(&LinkedNode::new(source.root()), true, &mut refs);
+    refs
+}
+
+fn collect_packages(node: &LinkedNode, specs: &mut Vec<PackageSpec>) {
+    if matches!(
+        node.kind(),
+        SyntaxKind::ModuleImport | SyntaxKind::ModuleInclude
+    ) {
+        let spec = node
+            .children()
+            .find(|child| child.kind() == SyntaxKind::Str)
+            .and_then(|child| PackageSpec::from_str(&child.cast::<ast::Str>()?.get()).ok());
+        specs.extend(spec);
+    }
+
+    for child in node.children() {
+        collect_packages(&child, specs);
+    }
+}
+
+/// Packages imported or included by a source, like `@preview/cetz:0.3.1`.
+pub fn package_imports(source: &Source) -> Vec<PackageSpec> {
+    let mut specs = Vec::new();
+    collect_packages(&LinkedNode::new(source.root()), &mut specs);
+    specs
+}
+
+/// What the path in a string literal is loaded as.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PathUse {
+    Module, // `import` and `include`
+    Data,   // `image`, `read` and the other file loading functions
+}
+
+/// The path use and the text typed so far if the cursor is inside a path literal.
+pub fn path_at(source: &Source, offset: usize) -> Option<(PathUse, String)> {
+    let leaf = LinkedNode::new(source.root()).leaf_at(offset, Side::Before)?;
+    if leaf.kind() != SyntaxKind::Str || offset <= leaf.offset() || offset >= leaf.range().end {
+        return None;
+    }
+
+    let parent = leaf.parent()?;
+    let call = match parent.kind() {
+        SyntaxKind::Args => parent.parent()?,
+        _ => parent,
+    };
+    if path_literal(call)?.offset() != leaf.offset() {
+        return None;
+    }
+
+    let path_use = match call.kind() {
+        SyntaxKind::FuncCall => PathUse::Data,
+        _ => PathUse::Module,
+    };
+    let typed = source.get(leaf.offset() + 1..offset)?;
+    // Package imports are completed by typst-ide
+    (!typed.starts_with('@')).then(|| (path_use, typed.to_string()))
+}
+
+/// The directory whose entries complete `typed`, written in the file `from`.
+pub fn completion_dir(from: &VirtualPath, typed: &str) -> String {
+    let written = typed.rsplit_once('/').map_or("", |(dir, _)| dir);
+    if typed.starts_with('/') {
+        normalize(written)
+    } else {
+        from.join(written)
+    }
+    .as_rooted_path()
+    .to_string_lossy()
+    .into_owned()
+}
+
+/// Completions for the entries of the directory typed so far, directories first, then the
+/// files that suit `path_use`.
+pub fn path_completions(
+    entries: Vec<DirEntry>,
+    typed: &str,
+    path_use: PathUse,
+    current: &VirtualPath,
+) -> Vec<Completion> {
+    let written = typed.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let current = current.as_rooted_path().to_string_lossy();
+    let preferred =
+        |entry: &DirEntry| entry.name.ends_with(".typ") == (path_use == PathUse::Module);
+
+    let mut entries = entries
+        .into_iter()
+        .filter(|entry| entry.path != current)
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then(preferred(b).cmp(&preferred(a)))
+            .then(a.name.cmp(&b.name))
+    });
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let name = if entry.is_dir {
+                format!("{}/", entry.name)
+            } else {
+                entry.name
+            };
+            Completion {
+                kind: CompletionDetail {
+                    kind: CompletionKind::Constant,
+                    detail: None,
+                },
+                apply: Some(if written.is_empty() && !typed.starts_with('/') {
+                    name.clone()
+                } else {
+                    format!("{}/{}", written, name)
+                }),
+                detail: Some(if entry.is_dir { "directory" } else { "file" }.to_string()),
+                label: name,
+            }
+        })
+        .collect()
+}
+
+fn import_info(
+    node: &LinkedNode,
+    source: &Source,
+    sources: &HashMap<FileId, FileEntry>,
+) -> Option<ImportInfo> {
+    let import = node.cast::<ast::ModuleImport>()?;
+    let (written, package, resolved) = match import.source() {
+        ast::Expr::Str(path) => {
+            let path = path.get();
+            match PackageSpec::from_str(&path) {
+                Ok(spec) => (path.to_string(), Some(spec), None),
+                Err(_) => {
+                    let id = FileId::new(None, source.id().vpath().join(path.as_str()));
+                    let resolved = sources
+                        .contains_key(&id)
+                        .then(|| qualified_path(id).to_string_lossy().into_owned());
+                    (path.to_string(), None, resolved)
+                }
+            }
+        }
+        // Modules imported by value, like `import sym`
+        expr => (
+            LinkedNode::new(expr.to_untyped())
+                .get()
+                .clone()
+                .into_text()
+                .to_string(),
+            None,
+            None,
+        ),
+    };
+
+    let names = match import.imports() {
+        Some(ast::Imports::Wildcard) => vec!["*".to_string()],
+        Some(ast::Imports::Items(items)) => items
+            .iter()
+            .map(|item| {
+                match item {
+                    ast::ImportItem::Simple(path) => path.to_untyped(),
+                    ast::ImportItem::Renamed(renamed) => renamed.to_untyped(),
+                }
+                .clone()
+                .into_text()
+                .to_string()
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Some(ImportInfo {
+        source: written,
+        package: package.as_ref().map(|spec| spec.to_string()),
+        package_spec: package.map(|spec| RawPackageSpec {
+            namespace: spec.namespace.to_string(),
+            name: spec.name.to_string(),
+            version: spec.version.to_string(),
+            description: None,
+        }),
+        registered: false,
+        resolved,
+        names,
+        new_name: import.new_name().map(|name| name.get().to_string()),
+        span: ResolvedSpan::from_source(node.span(), source),
+    })
+}
+
+fn collect_imports(
+    node: &LinkedNode,
+    source: &Source,
+    sources: &HashMap<FileId, FileEntry>,
+    imports: &mut Vec<ImportInfo>,
+) {
+    imports.extend(import_info(node, source, sources));
+    for child in node.children() {
+        collect_imports(&child, source, sources, imports);
+    }
+}
+
+/// The import statements of a source in document order, project files are resolved
+/// against `sources`.
+pub fn imports(source: &Source, sources: &HashMap<FileId, FileEntry>) -> Vec<ImportInfo> {
+    let mut imports = Vec::new();
+    collect_imports(
+        &LinkedNode::new(source.root()),
+        source,
+        sources,
+        &mut imports,
+    );
+    imports
+}
+
+/// Path of `to` relative to the directory of the file `from`, both rooted in the project.
+pub fn relative_path(from: &VirtualPath, to: &VirtualPath) -> String {
+    let from_dir = from.as_rooted_path().parent().unwrap_or(Path::new("/"));
+    let from_dir = from_dir.components().collect::<Vec<Component>>();
+    let to = to.as_rooted_path().components().collect::<Vec<Component>>();
+
+    let common = from_dir
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from_dir.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Rooted path of a file, package files are placed under `/@namespace/name:version/`.
+pub fn qualified_path(id: FileId) -> PathBuf {
+    match id.package() {
+        Some(spec) => Path::new("/")
+            .join(spec.to_string())
+            .join(id.vpath().as_rootless_path()),
+        None => id.vpath().as_rooted_path().to_path_buf(),
+    }
+}
+
+/// A rooted path with `.` and `..` resolved, `..` never leaves the root.
+pub fn normalize(path: &str) -> VirtualPath {
+    let mut normalized = PathBuf::from("/");
+    for component in Path::new(path).components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    VirtualPath::new(normalized)
+}
+
+/// Parses a path as produced by `qualified_path`. `..` cannot leave the project or package
+/// root, a leading slash is optional.
+pub fn parse_qualified_path(path: &str) -> FileId {
+    let rootless = path.trim_start_matches('/');
+    if rootless.starts_with('@') {
+        let mut parts = rootless.splitn(3, '/');
+        if let (Some(namespace), Some(name)) = (parts.next(), parts.next()) {
+            let spec = PackageSpec::from_str(&format!("{}/{}", namespace, name));
+            if let Ok(spec) = spec {
+                let path = normalize(parts.next().unwrap_or_default());
+                return FileId::new(Some(spec), path);
+            }
+        }
+    }
+    FileId::new(None, normalize(path))
+}
+
+/// A typst string literal for `text`.
+pub fn string_literal(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
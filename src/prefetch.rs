@@ -0,0 +1,308 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::RwLock;
+
+use typst::{
+    diag::{eco_format, EcoString, PackageError},
+    syntax::{
+        package::{PackageSpec, PackageVersion},
+        FileId,
+    },
+};
+
+use crate::{
+    fetch::fetch_bytes,
+    file_entry::FileEntry,
+    js_error_message,
+    js_types::{PrefetchReport, ProfilePhase, ResourceLimits},
+    lockfile, logging, package_index, paths,
+    profile::FetchPhases,
+    registry::{self, Registry},
+    ExtendedPackageVersion, PackageWrapper, TPComparable, TPFetchable, UnifiedPackageSpec,
+};
+
+type LatestVersions = HashMap<(EcoString, EcoString), PackageVersion>;
+
+/// Index of the registered package serving `spec`. Exact registrations (e.g. local packages)
+/// take precedence over latest ones.
+pub fn find_package(packages: &[PackageWrapper], spec: &PackageSpec) -> Option<usize> {
+    packages
+        .iter()
+        .position(|p| !p.is_latest() && spec.compare(p))
+        .or_else(|| packages.iter().position(|p| spec.compare(p)))
+}
+
+/// Highest version of a package known from the package index or other registrations.
+fn latest_known_version(
+    packages: &[PackageWrapper],
+    latest: &LatestVersions,
+    spec: &PackageSpec,
+) -> Option<PackageVersion> {
+    let registered = packages
+        .iter()
+        .filter(|p| p.namespace == spec.namespace && p.name == spec.name)
+        .filter_map(|p| match p.version {
+            ExtendedPackageVersion::Version(v) => Some(v),
+            ExtendedPackageVersion::Latest => None,
+        });
+
+    let known = latest
+        .get(&(spec.namespace.clone(), spec.name.clone()))
+        .copied();
+    known.into_iter().chain(registered).max()
+}
+
+/// Imports without a version stand for the newest one.
+fn is_placeholder(spec: &PackageSpec) -> bool {
+    spec.version == *ExtendedPackageVersion::Latest.version()
+}
+
+/// The version to download for an import of `spec` served by `wrapper`.
+fn version_to_fetch(
+    wrapper: &PackageWrapper,
+    packages: &[PackageWrapper],
+    latest: &LatestVersions,
+    registry: Option<&Registry>,
+    spec: &PackageSpec,
+) -> Result<PackageVersion, PackageError> {
+    match wrapper.version {
+        ExtendedPackageVersion::Version(v) => Ok(v),
+        ExtendedPackageVersion::Latest if !is_placeholder(spec) => Ok(spec.version),
+        ExtendedPackageVersion::Latest => wrapper
+            .resolved
+            .or_else(|| latest_known_version(packages, latest, spec))
+            .or_else(|| {
+                registry
+                    .is_some_and(Registry::is_versionless)
+                    .then_some(spec.version)
+            })
+            .ok_or_else(|| {
+                PackageError::Other(Some(eco_format!(
+                    "cannot resolve the latest version of @{}/{}, load the package index first",
+                    spec.namespace,
+                    spec.name
+                )))
+            }),
+    }
+}
+
+/// Shared state of a `SuiteCore`, so downloads can continue after the call returned.
+pub struct Prefetcher {
+    pub sources: Arc<RwLock<HashMap<FileId, FileEntry>>>,
+    pub packages: Arc<RwLock<Vec<PackageWrapper>>>,
+    pub latest_versions: Arc<RwLock<LatestVersions>>,
+    pub index_downloaded: Arc<AtomicBool>,
+    pub package_index_stale: Arc<AtomicBool>, // `SuiteCore::package_index` lists pinned versions
+    pub registries: Vec<Registry>,
+    pub profiling: Option<FetchPhases>,
+    pub limits: ResourceLimits,
+}
+
+impl Prefetcher {
+    /// Downloads the preview index once (until a package is invalidated), for resolving latest
+    /// versions without a loaded index.
+    async fn download_index(&self) {
+        if self.index_downloaded.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Ok(json) = fetch_bytes(package_index::PREVIEW_INDEX_URL.to_string()).await else {
+            return;
+        };
+        if let Ok(entries) = package_index::parse_index(&json) {
+            self.package_index_stale.store(true, Ordering::Relaxed);
+            let mut latest = self.latest_versions.write();
+            for entry in entries {
+                latest.insert(
+                    (
+                        EcoString::from(package_index::PREVIEW_NAMESPACE),
+                        EcoString::from(entry.name),
+                    ),
+                    entry.version,
+                );
+            }
+        }
+    }
+
+    /// Which package and version an import needs downloaded, `None` if nothing is to be done.
+    async fn plan(
+        &self,
+        spec: &PackageSpec,
+    ) -> Option<(usize, Result<PackageVersion, PackageError>)> {
+        for attempt in 0..2 {
+            {
+                let packages = self.packages.read();
+                // Unregistered packages are reported as not found when compiling
+                let index = find_package(&packages, spec)?;
+                let wrapper = &packages[index];
+
+                let cached = self
+                    .sources
+                    .read()
+                    .keys()
+                    .any(|id| id.package() == Some(spec));
+                let registry = registry::find(&self.registries, spec);
+                let refetch = wrapper.is_latest() && registry.is_some_and(|r| r.refetch_latest);
+                // Applying a lockfile or `invalidate_package` clears `fetched` to force a new download
+                if cached && wrapper.fetched && !refetch {
+                    return None;
+                }
+
+                let latest = self.latest_versions.read();
+                let version = version_to_fetch(wrapper, &packages, &latest, registry, spec);
+                let retry = attempt == 0
+                    && version.is_err()
+                    && spec.namespace == package_index::PREVIEW_NAMESPACE
+                    && !self.index_downloaded.load(Ordering::SeqCst);
+                if !retry {
+                    return Some((index, version));
+                }
+            }
+            self.download_index().await;
+        }
+        None
+    }
+
+    async fn download(
+        &self,
+        spec: &PackageSpec,
+        version: PackageVersion,
+        locked_hash: Option<String>,
+    ) -> Result<HashMap<FileId, FileEntry>, PackageError> {
+        let package = spec.to_string();
+        let url = registry::download_url(&self.registries, spec, &version);
+        logging::emit(
+            "package_fetch_start",
+            &logging::PackageFetchStart {
+                package: &package,
+                url: &url,
+            },
+        );
+        let started = js_sys::Date::now();
+
+        let data = fetch_bytes(url).await.map_err(|e| {
+            let message = js_error_message(&e);
+            logging::emit(
+                "package_fetch_failed",
+                &logging::PackageFetchFailed {
+                    package: &package,
+                    ms: js_sys::Date::now() - started,
+                    error: &message,
+                },
+            );
+            PackageError::NetworkFailed(Some(eco_format!(
+                "package {} could not be downloaded ({})",
+                spec,
+                message
+            )))
+        })?;
+        let ms = js_sys::Date::now() - started;
+        logging::emit(
+            "package_fetch_done",
+            &logging::PackageFetchDone {
+                package: &package,
+                ms,
+                bytes: data.len(),
+            },
+        );
+        if let Some(fetches) = &self.profiling {
+            fetches.lock().push(ProfilePhase {
+                name: format!("fetch {}", package),
+                ms,
+            });
+        }
+        let files = spec.extract(data, &self.limits)?;
+
+        let hash = lockfile::content_hash(&files);
+        if let Some(locked_hash) = locked_hash {
+            if locked_hash != hash {
+                return Err(PackageError::Other(Some(eco_format!(
+                    "content hash mismatch for {} (locked {}, fetched {})",
+                    spec,
+                    locked_hash,
+                    hash
+                ))));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Downloads every package imported by the project, and by those packages in turn.
+    /// Packages that are already cached are skipped until they get invalidated.
+    pub async fn prefetch(self) -> PrefetchReport {
+        let mut report = PrefetchReport {
+            downloaded: Vec::new(),
+            failed: Vec::new(),
+        };
+        let mut queue = self
+            .sources
+            .read()
+            .values()
+            .filter(|entry| entry.is_utf8())
+            .flat_map(|entry| paths::package_imports(&entry.source()))
+            .collect::<Vec<PackageSpec>>();
+        let mut seen = HashSet::new();
+
+        while let Some(spec) = queue.pop() {
+            if !seen.insert(spec.clone()) {
+                continue;
+            }
+            let Some((index, version)) = self.plan(&spec).await else {
+                continue;
+            };
+
+            let locked_hash = self.packages.read()[index].locked_hash.clone();
+            let result = match version {
+                Ok(version) => self
+                    .download(&spec, version, locked_hash)
+                    .await
+                    .map(|files| (version, files)),
+                Err(e) => Err(e),
+            };
+
+            // The list may have changed while downloading
+            let mut packages = self.packages.write();
+            let Some(wrapper) = find_package(&packages, &spec).map(|i| &mut packages[i]) else {
+                continue;
+            };
+            match result {
+                Ok((version, files)) => {
+                    wrapper.fetched = true;
+                    wrapper.resolved = Some(version);
+                    wrapper.hash = Some(lockfile::content_hash(&files));
+                    wrapper.error = None;
+                    // Pin latest wrappers to the concrete version that was imported
+                    let versionless = registry::find(&self.registries, &spec)
+                        .is_some_and(Registry::is_versionless);
+                    if !is_placeholder(&spec) && !versionless {
+                        wrapper.version = ExtendedPackageVersion::Version(version);
+                    }
+                    self.package_index_stale.store(true, Ordering::Relaxed);
+
+                    queue.extend(
+                        files
+                            .values()
+                            .filter(|entry| entry.is_utf8())
+                            .flat_map(|entry| paths::package_imports(&entry.source())),
+                    );
+                    let mut sources = self.sources.write();
+                    // Drop files a refreshed package no longer ships
+                    sources.retain(|id, _| id.package() != Some(&spec));
+                    sources.extend(files);
+                    report.downloaded.push(spec.to_string());
+                }
+                Err(e) => {
+                    report.failed.push(format!("{}: {}", spec, e));
+                    wrapper.error = Some(e);
+                }
+            }
+        }
+
+        report
+    }
+}
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+use typst::{
+    foundations::Smart,
+    layout::{Frame, FrameItem, Page},
+    visualize::{Color, Oklab, Paint},
+};
+
+use crate::js_types::PreviewOptions;
+
+/// Parsed `PreviewOptions`, applied to copies of the pages right before they are rendered.
+#[derive(Clone, Default)]
+pub struct PreviewStyle {
+    page_fill: Option<Option<Color>>, // `Some(None)` is transparent
+    text_invert: bool,
+}
+
+impl PreviewStyle {
+    pub fn from_options(options: &PreviewOptions) -> Result<Self, String> {
+        let page_fill = match options.page_fill.as_deref() {
+            None => None,
+            Some("transparent") => Some(None),
+            Some(hex) => Some(Some(Color::from_str(hex).map_err(|e| {
+                format!(
+                    "InvalidColor: page_fill '{}' is neither a hex color nor \"transparent\" ({})",
+                    hex, e
+                )
+            })?)),
+        };
+
+        Ok(Self {
+            page_fill,
+            text_invert: options.text_invert,
+        })
+    }
+
+    /// The page as the preview shows it.
+    pub fn apply(&self, page: &Page) -> Page {
+        let mut page = page.clone();
+        if let Some(fill) = self.page_fill {
+            page.fill = Smart::Custom(fill.map(Paint::Solid));
+        }
+        if self.text_invert {
+            invert_text(&mut page.frame);
+        }
+        page
+    }
+}
+
+/// Flips light and dark while keeping the hue, so colored text stays recognizable.
+fn invert_lightness(color: Color) -> Color {
+    let [l, a, b, alpha] = color.to_oklab().to_vec4();
+    Color::Oklab(Oklab::new(1.0 - l, a, b, alpha))
+}
+
+/// Inverts the solid fills and strokes of all text in a frame, gradients and patterns are kept.
+fn invert_text(frame: &mut Frame) {
+    let items = frame.items().cloned().collect::<Vec<_>>();
+    frame.clear();
+    frame.push_multiple(items.into_iter().map(|(pos, mut item)| {
+        match &mut item {
+            FrameItem::Group(group) => invert_text(&mut group.frame),
+            FrameItem::Text(text) => {
+                if let Paint::Solid(color) = text.fill {
+                    text.fill = Paint::Solid(invert_lightness(color));
+                }
+                if let Some(stroke) = &mut text.stroke {
+                    if let Paint::Solid(color) = stroke.paint {
+                        stroke.paint = Paint::Solid(invert_lightness(color));
+                    }
+                }
+            }
+            _ => {}
+        }
+        (pos, item)
+    }));
+}
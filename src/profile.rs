@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::js_types::{ProfilePhase, ProfileReport};
+
+/// Package fetches measured since the last compile, shared with running prefetches.
+pub type FetchPhases = Arc<Mutex<Vec<ProfilePhase>>>;
+
+/// Durations of the phases of one compile. Disabled profiles only call the measured code.
+pub struct Profile {
+    phases: Option<Vec<ProfilePhase>>,
+    started: f64,
+}
+
+impl Profile {
+    /// Starts with the package fetches recorded since the last compile, if profiling.
+    pub fn start(fetches: Option<&FetchPhases>) -> Self {
+        Self {
+            phases: fetches.map(|fetches| std::mem::take(&mut *fetches.lock())),
+            started: fetches.map_or(0.0, |_| js_sys::Date::now()),
+        }
+    }
+
+    pub fn measure<T>(&mut self, name: impl FnOnce() -> String, f: impl FnOnce() -> T) -> T {
+        let Some(phases) = &mut self.phases else {
+            return f();
+        };
+        let start = js_sys::Date::now();
+        let result = f();
+        phases.push(ProfilePhase {
+            name: name(),
+            ms: js_sys::Date::now() - start,
+        });
+        result
+    }
+
+    pub fn finish(self) -> Option<ProfileReport> {
+        Some(ProfileReport {
+            phases: self.phases?,
+            total_ms: js_sys::Date::now() - self.started,
+        })
+    }
+}
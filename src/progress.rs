@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use js_sys::Function;
+use wasm_bindgen::JsValue;
+
+/// JS callback receiving `(stage, done, total)` while a compile runs.
+pub struct ProgressCallback {
+    callback: Function,
+    parsed: AtomicBool, // Whether "parse" was already reported for the current compile
+}
+
+// SAFETY: wasm32 is single threaded, the callback never actually crosses a thread boundary.
+// This is only needed because `World` requires `Send + Sync`.
+unsafe impl Send for ProgressCallback {}
+unsafe impl Sync for ProgressCallback {}
+
+impl ProgressCallback {
+    pub fn new(callback: Function) -> Self {
+        Self {
+            callback,
+            parsed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn start(&self) {
+        self.parsed.store(false, Ordering::Relaxed);
+        self.report("start", 0, 0);
+    }
+
+    /// Reports the parsed main file, only the first time per compile.
+    pub fn parsed(&self) {
+        if !self.parsed.swap(true, Ordering::Relaxed) {
+            self.report("parse", 1, 1);
+        }
+    }
+
+    pub fn report(&self, stage: &str, done: usize, total: usize) {
+        // Exceptions thrown by the callback must not abort the compile
+        let _ = self.callback.call3(
+            &JsValue::NULL,
+            &JsValue::from_str(stage),
+            &JsValue::from(done as u32),
+            &JsValue::from(total as u32),
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use typst::{
+    layout::{FrameItem, Transform},
+    model::Document,
+    syntax::{FileId, Span, SyntaxKind},
+    text::TextItem,
+};
+
+use crate::{
+    file_entry::FileEntry,
+    frames::walk_items,
+    js_types::{ResolvedSpan, TextRun},
+};
+
+/// Whether a span points to markup text of a project file, not to code, math or raw text.
+fn is_prose(span: Span, sources: &HashMap<FileId, FileEntry>) -> bool {
+    let Some(entry) = span
+        .id()
+        .filter(|id| id.package().is_none())
+        .and_then(|id| sources.get(&id))
+    else {
+        return false;
+    };
+    let Some(node) = entry.source.find(span) else {
+        return false;
+    };
+    if node.kind() != SyntaxKind::Text {
+        return false;
+    }
+
+    let mut parent = node.parent().cloned();
+    while let Some(node) = parent {
+        if matches!(
+            node.kind(),
+            SyntaxKind::Raw | SyntaxKind::Equation | SyntaxKind::Math
+        ) {
+            return false;
+        }
+        parent = node.parent().cloned();
+    }
+    true
+}
+
+/// Layout inserts the hyphen of a word broken at the line end as a glyph without text.
+fn is_hyphenated(text: &TextItem) -> bool {
+    text.glyphs
+        .last()
+        .is_some_and(|glyph| glyph.range.is_empty())
+}
+
+/// The prose of the document in reading order, one run per laid out piece of text. `page`
+/// limits the runs to a single page.
+pub fn extract(
+    doc: &Document,
+    page: Option<usize>,
+    sources: &HashMap<FileId, FileEntry>,
+) -> Vec<TextRun> {
+    let mut runs: Vec<TextRun> = Vec::new();
+    let mut joins_next = false;
+
+    for (i, frame) in doc.pages.iter().map(|page| &page.frame).enumerate() {
+        if page.is_some_and(|page| page != i) {
+            continue;
+        }
+
+        walk_items(frame, Transform::identity(), &mut |item, _| {
+            let FrameItem::Text(text) = item else {
+                return;
+            };
+            let Some(span) = text
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.span.0)
+                .find(|span| !span.is_detached())
+            else {
+                return;
+            };
+            if !is_prose(span, sources) {
+                joins_next = false;
+                return;
+            }
+
+            match runs.last_mut().filter(|_| joins_next) {
+                Some(run) => run.text.push_str(&text.text),
+                None => runs.push(TextRun {
+                    text: text.text.to_string(),
+                    page: i,
+                    span: ResolvedSpan::from_sources(span, sources),
+                }),
+            }
+            joins_next = is_hyphenated(text);
+        });
+    }
+
+    runs
+}
@@ -0,0 +1,47 @@
+use comemo::Track;
+use typst::{
+    eval::{eval_string, EvalMode},
+    foundations::{IntoValue, LocatableSelector, Scope, Value},
+    model::Document,
+    syntax::Span,
+    World,
+};
+
+/// Evaluates `selector` as code, e.g. `<key>` or `heading.where(level: 1)`, and returns the
+/// matching elements of `document` as a JSON array, like `typst query`. With a `field`, only
+/// that field of each element is returned and elements without it are skipped.
+pub fn query(
+    world: &dyn World,
+    document: &Document,
+    selector: &str,
+    field: Option<&str>,
+) -> Result<String, String> {
+    let selector = eval_string(
+        world.track(),
+        selector,
+        Span::detached(),
+        EvalMode::Code,
+        Scope::default(),
+    )
+    .map_err(|errors| {
+        errors
+            .iter()
+            .map(|error| error.message.to_string())
+            .collect::<Vec<String>>()
+            .join("; ")
+    })?
+    .cast::<LocatableSelector>()
+    .map_err(|e| e.message().to_string())?;
+
+    let values = document
+        .introspector
+        .query(&selector.0)
+        .into_iter()
+        .filter_map(|content| match field {
+            Some(field) => content.get_by_name(field).ok(),
+            None => Some(content.into_value()),
+        })
+        .collect::<Vec<Value>>();
+
+    serde_json::to_string(&values).map_err(|e| e.to_string())
+}
@@ -0,0 +1,192 @@
+use typst::{
+    foundations::{Scope, Value},
+    syntax::{ast, FileId, LinkedNode, Source, Span, SyntaxKind},
+    Library,
+};
+
+use crate::js_types::{FileTextEdit, QuickFix};
+
+/// Suggestions offered for a misspelled name.
+const MAX_SUGGESTIONS: usize = 3;
+
+fn edit(id: FileId, start: usize, end: usize, text: impl Into<String>) -> FileTextEdit {
+    FileTextEdit {
+        file_path: id.vpath().as_rooted_path().to_string_lossy().into_owned(),
+        start_offset: start,
+        end_offset: end,
+        insert_text: text.into(),
+    }
+}
+
+/// Number of single character insertions, deletions and substitutions turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Names bound by `let` anywhere in the source, typst has no scope information for them
+/// after a failed compile.
+fn collect_bindings(node: &LinkedNode, names: &mut Vec<String>) {
+    if let Some(binding) = node.cast::<ast::LetBinding>() {
+        names.extend(
+            binding
+                .kind()
+                .bindings()
+                .into_iter()
+                .map(|ident| ident.get().to_string()),
+        );
+    }
+    for child in node.children() {
+        collect_bindings(&child, names);
+    }
+}
+
+fn scope_names(scope: &Scope) -> impl Iterator<Item = String> + '_ {
+    scope.iter().map(|(name, _, _)| name.to_string())
+}
+
+/// Replacements for an unknown variable with a similar name in scope.
+fn similar_names(
+    name: &str,
+    node: &LinkedNode,
+    source: &Source,
+    library: &Library,
+) -> Vec<QuickFix> {
+    let mut candidates = Vec::new();
+    collect_bindings(&LinkedNode::new(source.root()), &mut candidates);
+    candidates.extend(scope_names(library.global.scope()));
+    if node.kind() == SyntaxKind::MathIdent {
+        candidates.extend(scope_names(library.math.scope()));
+    }
+
+    let max = if name.chars().count() <= 4 { 1 } else { 2 };
+    let mut matches = candidates
+        .into_iter()
+        .filter(|candidate| candidate != name)
+        .filter_map(|candidate| {
+            let distance = edit_distance(name, &candidate);
+            (distance <= max).then_some((distance, candidate))
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup_by(|(_, a), (_, b)| a == b);
+
+    let range = node.range();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| QuickFix {
+            title: format!("Replace with `{}`", candidate),
+            edits: vec![edit(source.id(), range.start, range.end, candidate)],
+        })
+        .collect()
+}
+
+/// Qualified names and imports for an unknown variable that a standard library module
+/// like `calc` defines.
+fn module_members(
+    name: &str,
+    node: &LinkedNode,
+    source: &Source,
+    library: &Library,
+) -> Vec<QuickFix> {
+    let range = node.range();
+    let mut fixes = Vec::new();
+    for (module_name, value, _) in library.global.scope().iter() {
+        let Value::Module(module) = value else {
+            continue;
+        };
+        if module.scope().get(name).is_none() {
+            continue;
+        }
+
+        fixes.push(QuickFix {
+            title: format!("Replace with `{}.{}`", module_name, name),
+            edits: vec![edit(
+                source.id(),
+                range.start,
+                range.end,
+                format!("{}.{}", module_name, name),
+            )],
+        });
+        fixes.push(QuickFix {
+            title: format!("Import `{}` from `{}`", name, module_name),
+            edits: vec![edit(
+                source.id(),
+                0,
+                0,
+                format!("#import {}: {}\n", module_name, name),
+            )],
+        });
+    }
+    fixes
+}
+
+/// The text closing an unclosed delimiter, string, raw block or label, and where it goes.
+fn closing(node: &LinkedNode, message: &str) -> Option<(usize, String)> {
+    let text = node.text();
+    let end = node.range().end;
+    match message {
+        "unclosed string" => Some((end, "\"".into())),
+        "unclosed label" => Some((end, ">".into())),
+        "unclosed raw text" => {
+            let ticks = text.chars().take_while(|&c| c == '`').count();
+            Some((end, "`".repeat(ticks)))
+        }
+        // The error is the opening delimiter, the unclosed group is its parent
+        "unclosed delimiter" => {
+            let close = match text.as_str() {
+                "(" => ")",
+                "[" => "]",
+                "{" => "}",
+                "$" => "$",
+                "*" => "*",
+                "_" => "_",
+                _ => return None,
+            };
+            let end = node.parent().map_or(end, |parent| parent.range().end);
+            Some((end, close.into()))
+        }
+        _ => None,
+    }
+}
+
+/// Best effort fixes for a diagnostic at `span` in `source`. `library` enables the
+/// suggestions for unknown variables, syntax errors need only the source.
+pub fn fixes(
+    message: &str,
+    span: Span,
+    source: &Source,
+    library: Option<&Library>,
+) -> Vec<QuickFix> {
+    let Some(node) = LinkedNode::new(source.root()).find(span) else {
+        return Vec::new();
+    };
+
+    if let Some(name) = message.strip_prefix("unknown variable: ") {
+        let Some(library) = library else {
+            return Vec::new();
+        };
+        let mut fixes = module_members(name, &node, source, library);
+        fixes.extend(similar_names(name, &node, source, library));
+        return fixes;
+    }
+
+    match closing(&node, message) {
+        Some((offset, text)) => vec![QuickFix {
+            title: format!("Insert `{}`", text),
+            edits: vec![edit(source.id(), offset, offset, text)],
+        }],
+        None => Vec::new(),
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use typst::{
+    model::Document,
+    syntax::{ast, FileId, LinkedNode, Side, Source, Span, SyntaxKind},
+    World,
+};
+use typst_ide::Definition;
+
+use crate::{
+    file_entry::FileEntry,
+    js_types::{Reference, ResolvedSpan},
+};
+
+/// What the symbol under the cursor stands for.
+enum Target {
+    Label(String), // Without the angle brackets
+    Definition(Definition),
+}
+
+/// Name of the label declared (`<name>`) or referenced (`@name`) at the cursor, and whether
+/// it is the declaration.
+fn label_at(source: &Source, offset: usize) -> Option<(String, bool)> {
+    let root = LinkedNode::new(source.root());
+    let leaf = root.leaf_at(offset, Side::After)?;
+
+    if leaf.kind() == SyntaxKind::Label {
+        return Some((label_name(&leaf), true));
+    }
+    let node = if leaf.kind() == SyntaxKind::Ref {
+        leaf
+    } else if leaf.parent_kind() == Some(SyntaxKind::Ref) {
+        leaf.parent()?.clone()
+    } else {
+        return None;
+    };
+    Some((node.cast::<ast::Ref>()?.target().to_string(), false))
+}
+
+fn label_name(node: &LinkedNode) -> String {
+    node.text()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+/// Whether two definitions point to the same binding. Definitions without any location
+/// (like builtins) are compared by name.
+fn same_definition(a: &Definition, b: &Definition) -> bool {
+    if !a.name_span.is_detached() {
+        a.name_span == b.name_span
+    } else if !a.span.is_detached() {
+        a.span == b.span
+    } else {
+        a.name == b.name && a.kind == b.kind && b.span.is_detached() && b.name_span.is_detached()
+    }
+}
+
+struct Collector<'a> {
+    world: &'a dyn World,
+    document: Option<&'a Document>,
+    target: &'a Target,
+    references: Vec<Reference>,
+}
+
+impl Collector<'_> {
+    fn push(&mut self, span: Span, source: &Source, is_definition: bool) {
+        self.references.push(Reference {
+            span: ResolvedSpan::from_source(span, source),
+            is_definition,
+            package: source.id().package().map(|spec| spec.to_string()),
+        });
+    }
+
+    fn collect(&mut self, node: &LinkedNode, source: &Source) {
+        match (self.target, node.kind()) {
+            (Target::Label(name), SyntaxKind::Label) if label_name(node) == *name => {
+                self.push(node.span(), source, true);
+            }
+            (Target::Label(name), SyntaxKind::Ref)
+                if node.cast::<ast::Ref>().is_some_and(|r| r.target() == name) =>
+            {
+                self.push(node.span(), source, false);
+            }
+            (Target::Definition(def), SyntaxKind::Ident | SyntaxKind::MathIdent)
+                if node.text() == &def.name =>
+            {
+                if node.span() == def.name_span {
+                    self.push(node.span(), source, true);
+                } else if typst_ide::definition(
+                    self.world,
+                    self.document,
+                    source,
+                    node.offset(),
+                    Side::After,
+                )
+                .is_some_and(|other| same_definition(def, &other))
+                {
+                    self.push(node.span(), source, false);
+                }
+            }
+            _ => {}
+        }
+
+        for child in node.children() {
+            self.collect(&child, source);
+        }
+    }
+}
+
+/// Collects the uses of `target` across `sources`, sorted by package, file and offset.
+fn collect(
+    world: &dyn World,
+    document: Option<&Document>,
+    sources: &HashMap<FileId, FileEntry>,
+    target: &Target,
+) -> Vec<Reference> {
+    let mut collector = Collector {
+        world,
+        document,
+        target,
+        references: Vec::new(),
+    };
+    for entry in sources.values().filter(|entry| entry.is_utf8()) {
+        collector.collect(&LinkedNode::new(entry.source.root()), &entry.source);
+    }
+
+    let mut references = collector.references;
+    references.sort_by(|a, b| {
+        a.package
+            .cmp(&b.package)
+            .then(a.span.file_path.cmp(&b.span.file_path))
+            .then(a.span.start_offset.cmp(&b.span.start_offset))
+    });
+    references
+}
+
+/// Every use of the label or symbol at `offset` across `sources`, including its definition.
+pub fn find_references(
+    world: &dyn World,
+    document: Option<&Document>,
+    sources: &HashMap<FileId, FileEntry>,
+    source: &Source,
+    offset: usize,
+) -> Vec<Reference> {
+    let target = match label_at(source, offset) {
+        Some((name, _)) => Target::Label(name),
+        None => match typst_ide::definition(world, document, source, offset, Side::After) {
+            Some(def) => Target::Definition(def),
+            None => return Vec::new(),
+        },
+    };
+
+    collect(world, document, sources, &target)
+}
+
+/// Where a jump from the label or reference at `offset` can land: the declarations of a
+/// referenced label, or the references to a declared one.
+pub fn label_jump(
+    world: &dyn World,
+    sources: &HashMap<FileId, FileEntry>,
+    source: &Source,
+    offset: usize,
+) -> Vec<Reference> {
+    let Some((name, declaration)) = label_at(source, offset) else {
+        return Vec::new();
+    };
+
+    collect(world, None, sources, &Target::Label(name))
+        .into_iter()
+        .filter(|reference| reference.is_definition != declaration)
+        .collect()
+}
@@ -0,0 +1,61 @@
+use typst::syntax::package::{PackageSpec, PackageVersion};
+
+/// Archive URL of packages not served by a registered registry.
+const PREVIEW_URL_TEMPLATE: &str = "https://packages.typst.org/preview/{name}-{version}.tar.gz";
+
+/// A package registry serving the namespaces starting with `prefix`.
+#[derive(Clone, Debug)]
+pub struct Registry {
+    pub prefix: String,
+    pub url_template: String, // With `{namespace}`, `{scope}`, `{name}` and `{version}` placeholders
+    pub refetch_latest: bool, // Re-download latest packages on every prefetch
+}
+
+impl Registry {
+    /// Whether the registry serves the newest version when asked without one.
+    pub fn is_versionless(&self) -> bool {
+        !self.url_template.contains("{version}")
+    }
+
+    fn url(&self, spec: &PackageSpec, version: &PackageVersion) -> String {
+        fill_template(&self.url_template, &self.prefix, spec, version)
+    }
+}
+
+/// `{scope}` is the namespace without the registry prefix and its separating dash.
+fn fill_template(
+    template: &str,
+    prefix: &str,
+    spec: &PackageSpec,
+    version: &PackageVersion,
+) -> String {
+    let scope = spec
+        .namespace
+        .strip_prefix(prefix)
+        .unwrap_or(&spec.namespace);
+    template
+        .replace("{namespace}", &spec.namespace)
+        .replace("{scope}", scope.trim_start_matches('-'))
+        .replace("{name}", &spec.name)
+        .replace("{version}", &version.to_string())
+}
+
+/// The registry serving `spec`, the one with the longest matching prefix wins.
+pub fn find<'a>(registries: &'a [Registry], spec: &PackageSpec) -> Option<&'a Registry> {
+    registries
+        .iter()
+        .filter(|r| spec.namespace.starts_with(r.prefix.as_str()))
+        .max_by_key(|r| r.prefix.len())
+}
+
+/// Where `version` of `spec` is downloaded from, packages.typst.org unless registered otherwise.
+pub fn download_url(
+    registries: &[Registry],
+    spec: &PackageSpec,
+    version: &PackageVersion,
+) -> String {
+    match find(registries, spec) {
+        Some(registry) => registry.url(spec, version),
+        None => fill_template(PREVIEW_URL_TEMPLATE, "", spec, version),
+    }
+}
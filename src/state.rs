@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the snapshot format, bumped on incompatible changes.
+pub const STATE_VERSION: u32 = 1;
+
+/// Everything about a project that is not embedded in the binary. Fonts and the standard
+/// library are rebuilt by `SuiteCore::new` and left out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProjectState {
+    pub version: u32,
+    pub root: String,
+    pub files: Vec<StateFile>,
+    pub packages: Vec<StatePackage>,
+    pub latest_versions: Vec<StateLatest>,
+    pub registries: Vec<StateRegistry>,
+    pub inputs: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateFile {
+    pub path: String, // Qualified path, package files live under `/@namespace/name:version/`
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatePackage {
+    pub namespace: String,
+    pub name: String,
+    pub version: String, // The version as it was registered (may be "latest")
+    pub fetched: bool,
+    pub description: Option<String>,
+    pub resolved: Option<String>,
+    pub hash: Option<String>,
+    pub locked_hash: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateLatest {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateRegistry {
+    pub prefix: String,
+    pub url_template: String,
+    pub refetch_latest: bool,
+}
+
+impl ProjectState {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let state: Self = rmp_serde::from_slice(data).map_err(|e| e.to_string())?;
+        if state.version != STATE_VERSION {
+            return Err(format!(
+                "unsupported state version {} (expected {})",
+                state.version, STATE_VERSION
+            ));
+        }
+        Ok(state)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        rmp_serde::to_vec_named(self).map_err(|e| e.to_string())
+    }
+}
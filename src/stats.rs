@@ -0,0 +1,96 @@
+use typst::{
+    layout::{Abs, FrameItem, Point, Transform},
+    model::Document,
+};
+
+use crate::{frames::walk_items, js_types::DocumentStats};
+
+/// Scripts written without spaces between words, every character counts as a word.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}' // Halfwidth Katakana
+        | '\u{20000}'..='\u{2FA1F}' // CJK Extensions B-F, Compatibility Supplement
+    )
+}
+
+fn count_words(text: &str) -> usize {
+    let mut words = 0;
+    let mut in_word = false;
+    for c in text.chars() {
+        if is_cjk(c) {
+            words += 1;
+            in_word = false;
+        } else if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word && c.is_alphanumeric() {
+            words += 1;
+            in_word = true;
+        }
+    }
+    words
+}
+
+/// Statistics over the laid out text of a document, so markup, code and comments are not counted.
+pub fn document_stats(doc: &Document) -> DocumentStats {
+    let mut text = String::new();
+    let mut paragraphs = 0;
+    let mut paragraph_has_text = false;
+
+    for page in &doc.pages {
+        let mut prev: Option<(Abs, Abs)> = None; // Baseline and font size of the previous run
+
+        walk_items(&page.frame, Transform::identity(), &mut |item, ts| {
+            let FrameItem::Text(item) = item else {
+                return;
+            };
+            let baseline = Point::zero().transform(ts).y;
+
+            let (new_line, new_paragraph) = match prev {
+                None => (true, true),
+                Some((prev_baseline, prev_size)) => {
+                    let size = prev_size.min(item.size);
+                    let advance = baseline - prev_baseline;
+                    let new_line = advance.abs() > size * 0.5;
+                    // Lines of a paragraph are about 1.3em apart, paragraphs about 1.9em.
+                    // A line in another font size (e.g. after a heading) also starts a new one.
+                    (
+                        new_line,
+                        advance > size * 1.6
+                            || advance < -size * 0.5
+                            || (new_line && prev_size != item.size),
+                    )
+                }
+            };
+            prev = Some((baseline, item.size));
+
+            if new_paragraph && paragraph_has_text {
+                paragraphs += 1;
+                paragraph_has_text = false;
+            }
+            // Line breaks drop the space between words, add it back
+            if new_line && !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&item.text);
+            paragraph_has_text |= item.text.chars().any(|c| !c.is_whitespace());
+        });
+
+        // Paragraphs don't continue over page breaks for counting purposes
+        if paragraph_has_text {
+            paragraphs += 1;
+            paragraph_has_text = false;
+        }
+    }
+
+    DocumentStats {
+        words: count_words(&text),
+        characters: text.chars().filter(|c| *c != '\n').count(),
+        characters_no_spaces: text.chars().filter(|c| !c.is_whitespace()).count(),
+        pages: doc.pages.len(),
+        paragraphs,
+    }
+}
@@ -0,0 +1,200 @@
+use typst::{
+    foundations::{Func, Scope, Value},
+    syntax::ast::{MathShorthand, Shorthand},
+    Library,
+};
+
+use crate::{
+    js_types::{LibrarySymbol, SymbolInfo, TidyDocs, TidyType},
+    tidy::{builtin_docs, first_paragraph},
+};
+
+/// Library modules whose symbols are searchable.
+const SYMBOL_MODULES: &[&str] = &["sym", "emoji"];
+
+fn shorthand(list: &[(&str, char)], c: char) -> Option<String> {
+    list.iter()
+        .find(|(_, shorthand)| *shorthand == c)
+        .map(|(text, _)| text.to_string())
+}
+
+/// Every symbol variant of the searchable modules, named like `sym.arrow.r`.
+fn all_symbols(library: &Library) -> Vec<SymbolInfo> {
+    let mut symbols = Vec::new();
+    for module in SYMBOL_MODULES {
+        let Some(Value::Module(scope)) = library.global.scope().get(module) else {
+            continue;
+        };
+        for (name, value, _) in scope.scope().iter() {
+            let Value::Symbol(symbol) = value else {
+                continue;
+            };
+            for (modifiers, c) in symbol.variants() {
+                let c = c.char();
+                symbols.push(SymbolInfo {
+                    name: if modifiers.is_empty() {
+                        format!("{}.{}", module, name)
+                    } else {
+                        format!("{}.{}.{}", module, name, modifiers)
+                    },
+                    char: c.to_string(),
+                    markup_shorthand: shorthand(Shorthand::LIST, c),
+                    math_shorthand: shorthand(MathShorthand::LIST, c),
+                });
+            }
+        }
+    }
+    symbols
+}
+
+/// Whether the characters of `query` appear in `name` in order.
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut chars = name.chars();
+    query.chars().all(|q| chars.any(|c| c == q))
+}
+
+/// Lower is better: exact, prefix, substring and fuzzy matches. `None` if nothing matches.
+/// Names are compared case-insensitively, the character only as typed.
+fn rank(symbol: &SymbolInfo, query: &str) -> Option<u8> {
+    let name = symbol.name.to_lowercase();
+    // The name without its module, as written in math (`arrow.r`)
+    let short = name.split_once('.').map_or(name.as_str(), |(_, rest)| rest);
+    let lower = query.to_lowercase();
+    let lower = lower.as_str();
+
+    if name == lower || short == lower || symbol.char == query {
+        Some(0)
+    } else if name.starts_with(lower) || short.starts_with(lower) {
+        Some(1)
+    } else if name.contains(lower) {
+        Some(2)
+    } else if is_subsequence(lower, &name) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+pub fn search(library: &Library, query: &str, limit: usize) -> Vec<SymbolInfo> {
+    let query = query.trim();
+    let mut matches = all_symbols(library)
+        .into_iter()
+        .filter_map(|symbol| Some((rank(&symbol, query)?, symbol)))
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|(a_rank, a), (b_rank, b)| {
+        a_rank
+            .cmp(b_rank)
+            .then(a.name.len().cmp(&b.name.len()))
+            .then(a.name.cmp(&b.name))
+    });
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, symbol)| symbol)
+        .collect()
+}
+
+/// The first sentence of builtin docs on a single line.
+fn summary(docs: &str) -> Option<String> {
+    let paragraph = first_paragraph(docs)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let sentence = match paragraph.find(". ") {
+        Some(end) => &paragraph[..=end],
+        None => paragraph.as_str(),
+    };
+    (!sentence.is_empty()).then(|| sentence.to_string())
+}
+
+fn param_names(func: &Func) -> Vec<String> {
+    func.params()
+        .unwrap_or_default()
+        .iter()
+        .map(|param| param.name.to_string())
+        .collect()
+}
+
+fn library_symbol(name: &str, value: &Value) -> LibrarySymbol {
+    let (kind, constructor) = match value {
+        Value::Func(func) => ("func", Some(func.clone())),
+        Value::Type(ty) => ("type", ty.constructor().ok()),
+        Value::Module(_) => ("module", None),
+        _ => ("value", None),
+    };
+
+    let description = value.docs().map(first_paragraph);
+    let docs = match &constructor {
+        Some(func) => builtin_docs(name.to_string(), func).map(|mut docs| {
+            // A type's own docs describe it better than those of its constructor
+            if let Some(description) = description.clone() {
+                docs.add_description(description);
+            }
+            docs
+        }),
+        None => description.clone().map(|description| {
+            let mut docs = TidyDocs::new(name.to_string(), TidyType::Variable);
+            docs.add_description(description);
+            docs
+        }),
+    };
+
+    LibrarySymbol {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        summary: value.docs().and_then(summary),
+        params: constructor.as_ref().map(param_names).unwrap_or_default(),
+        docs,
+    }
+}
+
+/// The bindings of the global scope, or of the module at the dotted path `module` like
+/// `calc` or `math`, in definition order.
+pub fn library_symbols(
+    library: &Library,
+    module: Option<&str>,
+) -> Result<Vec<LibrarySymbol>, String> {
+    let mut scope: &Scope = library.global.scope();
+    for part in module.into_iter().flat_map(|module| module.split('.')) {
+        match scope.get(part) {
+            Some(Value::Module(module)) => scope = module.scope(),
+            _ => {
+                return Err(format!(
+                    "`{}` is not a module of the standard library",
+                    module.unwrap_or_default()
+                ))
+            }
+        }
+    }
+
+    Ok(scope
+        .iter()
+        .map(|(name, value, _)| library_symbol(name, value))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(query: &str) -> Vec<String> {
+        let library = Library::builder().build();
+        search(&library, query, 3)
+            .into_iter()
+            .map(|symbol| symbol.name)
+            .collect()
+    }
+
+    #[test]
+    fn case_insensitive_names() {
+        assert_eq!(names("Alpha")[..2], ["sym.Alpha", "sym.alpha"]);
+        assert_eq!(names("ALPHA")[..2], ["sym.Alpha", "sym.alpha"]);
+    }
+
+    #[test]
+    fn exact_char() {
+        assert_eq!(names("Α")[0], "sym.Alpha");
+        assert_eq!(names("α")[0], "sym.alpha");
+    }
+}
@@ -1,19 +1,95 @@
-use typst::syntax::LinkedNode;
+use pulldown_cmark::{html, Event, Parser};
+use typst::{
+    foundations::{CastInfo, Func, Repr},
+    syntax::{LinkedNode, SyntaxNode},
+};
 
 use crate::{
-    ast::{get_args, get_prev, get_prev_kind, is_function, is_variable},
-    js_types, logWasm,
+    ast::{get_args, get_prev, is_function, is_variable},
+    js_types,
 };
 
+/// Longest parameter default shown in hover docs, in characters.
+const MAX_DEFAULT_LEN: usize = 60;
+
 fn parse_description(description: String) -> String {
-    description
-        .lines()
-        .filter(|s| s.starts_with("///"))
-        .map(|s| s.replace("///", "").trim().to_string())
-        .collect::<Vec<String>>()
-        .join("\n")
-        .trim()
-        .to_string()
+    let mut lines = Vec::new();
+    let mut in_block = false;
+    for line in description.lines().map(str::trim) {
+        let mut line = line;
+        let opens = line.starts_with("/**");
+        if opens {
+            in_block = true;
+            line = &line[3..];
+        } else if !in_block {
+            if line.starts_with("///") {
+                lines.push(line.replace("///", "").trim().to_string());
+            }
+            continue;
+        }
+
+        // Strip the `*/` and leading `*` decorations of block comments
+        let closes = line.ends_with("*/");
+        if closes {
+            in_block = false;
+            line = &line[..line.len() - 2];
+        }
+        let line = line.trim();
+        let line = line.strip_prefix('*').unwrap_or(line).trim();
+        // Lines holding only a delimiter are no paragraph breaks
+        if !(line.is_empty() && (opens || closes)) {
+            lines.push(line.to_string());
+        }
+    }
+
+    lines.join("\n").trim().to_string()
+}
+
+/// Parses a `- name (type): description` line, types may be separated by `,` or `|`.
+fn parse_arg_annotation(line: &str) -> Option<js_types::TidyArgDocs> {
+    let (head, description) = line.strip_prefix("- ")?.split_once(':')?;
+    let (name, types) = head.split_once('(')?;
+    let name = name.trim();
+    let types = types.trim().strip_suffix(')')?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+
+    let mut arg = js_types::TidyArgDocs::new(name.to_string());
+    types
+        .split([',', '|'])
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .for_each(|t| arg.add_type(t.to_string()));
+    arg.add_description(description.trim().to_string());
+    Some(arg)
+}
+
+/// Splits the argument annotations off a description. Lines following an annotation
+/// continue its description until the next blank line or annotation.
+fn split_arg_annotations(description: String) -> (String, Vec<js_types::TidyArgDocs>) {
+    let mut lines = Vec::new();
+    let mut args: Vec<js_types::TidyArgDocs> = Vec::new();
+    let mut continues = false;
+    for line in description.lines() {
+        if let Some(arg) = parse_arg_annotation(line) {
+            args.push(arg);
+            continues = true;
+        } else if continues && !line.is_empty() {
+            let arg = args.last_mut().unwrap();
+            let text = arg.description.take().unwrap_or_default();
+            arg.add_description(format!("{}\n{}", text, line).trim().to_string());
+        } else {
+            continues = false;
+            lines.push(line);
+        }
+    }
+
+    (lines.join("\n").trim().to_string(), args)
 }
 
 pub fn parse_doc_str(name: String, doc: js_types::TidyComments) -> js_types::TidyDocs {
@@ -36,6 +112,7 @@ pub fn parse_doc_str(name: String, doc: js_types::TidyComments) -> js_types::Tid
             (parse_description(doc.pre.to_string()), None)
         }
     };
+    let (description, mut annotations) = split_arg_annotations(description);
     docs.add_description(description);
     if return_types.is_some() {
         for return_type in return_types.unwrap() {
@@ -66,39 +143,186 @@ pub fn parse_doc_str(name: String, doc: js_types::TidyComments) -> js_types::Tid
         if let Some(default) = default {
             param.add_default(default);
         }
+        // Comments on the parameter itself take precedence over annotations
+        if let Some(i) = annotations.iter().position(|a| a.name == param.name) {
+            let annotation = annotations.remove(i);
+            if param.description.as_deref().is_none_or(str::is_empty) {
+                param.description = annotation.description;
+            }
+            if param.types.is_empty() {
+                param.types = annotation.types;
+            }
+        }
         docs.add_argument(param);
     }
+    for annotation in annotations {
+        docs.add_argument(annotation);
+    }
 
     docs
 }
 
+/// Renders a doc comment description as HTML. Raw HTML in the comment is escaped, since
+/// comments may come from fetched packages.
+pub fn markdown_to_html(text: &str) -> String {
+    let parser = Parser::new(text).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        event => event,
+    });
+    let mut result = String::new();
+    html::push_html(&mut result, parser);
+    result.trim_end().to_string()
+}
+
+/// First paragraph of builtin markdown docs, the rest is mostly examples.
+pub fn first_paragraph(docs: &str) -> String {
+    docs.split("\n\n")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Type names accepted by a builtin parameter or returned by a builtin function.
+fn cast_types(info: &CastInfo) -> Vec<String> {
+    let mut types = Vec::new();
+    info.walk(|info| {
+        let name = match info {
+            CastInfo::Any => "any".to_string(),
+            CastInfo::Value(value, _) => value.repr().to_string(),
+            CastInfo::Type(ty) => ty.short_name().to_string(),
+            CastInfo::Union(_) => return,
+        };
+        if !types.contains(&name) {
+            types.push(name);
+        }
+    });
+    types
+}
+
+/// Docs of a builtin function in the shape tidy comments are parsed into, so builtin and
+/// user-defined functions render alike. `None` for closures, which have no parameter info.
+pub fn builtin_docs(name: String, func: &Func) -> Option<js_types::TidyDocs> {
+    let params = func.params()?;
+    let mut docs = js_types::TidyDocs::new(name, js_types::TidyType::Function);
+
+    if let Some(description) = func.docs() {
+        docs.add_description(first_paragraph(description));
+    }
+    for return_type in func.returns().map(cast_types).unwrap_or_default() {
+        docs.add_return_type(return_type);
+    }
+
+    for param in params {
+        let mut arg = js_types::TidyArgDocs::new(param.name.to_string());
+        for type_ in cast_types(&param.input) {
+            arg.add_type(type_);
+        }
+        arg.add_description(first_paragraph(param.docs));
+        if let Some(default) = param.default {
+            arg.add_default(default().repr().to_string());
+        }
+        docs.add_argument(arg);
+    }
+
+    Some(docs)
+}
+
+/// Whitespace containing a line break, `\n` as well as `\r\n`.
+fn is_line_break(node: &LinkedNode) -> bool {
+    matches!(
+        node.kind(),
+        typst::syntax::SyntaxKind::Space | typst::syntax::SyntaxKind::Parbreak
+    ) && node.text().contains('\n')
+}
+
+/// Appends the text of `node` with whitespace runs collapsed to single spaces, except inside
+/// string literals where it is kept as written.
+fn push_collapsed(node: &SyntaxNode, text: &mut String, space: &mut bool) {
+    if node.kind() == typst::syntax::SyntaxKind::Str {
+        if std::mem::take(space) && !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(node.text());
+    } else if node.children().len() == 0 {
+        for c in node.text().chars() {
+            if c.is_whitespace() {
+                *space = true;
+            } else {
+                if std::mem::take(space) && !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push(c);
+            }
+        }
+    }
+    for child in node.children() {
+        push_collapsed(child, text, space);
+    }
+}
+
+/// The source of the default of a named parameter with collapsed whitespace, long
+/// expressions are cut off with an ellipsis.
+fn default_text(named: &LinkedNode) -> Option<String> {
+    let expr = named
+        .children()
+        .skip_while(|child| child.kind() != typst::syntax::SyntaxKind::Colon)
+        .skip(1)
+        .find(|child| !child.kind().is_trivia())?;
+    let mut text = String::new();
+    push_collapsed(expr.get(), &mut text, &mut false);
+
+    if text.chars().count() > MAX_DEFAULT_LEN {
+        let cut = text.chars().take(MAX_DEFAULT_LEN - 1).collect::<String>();
+        Some(format!("{}…", cut.trim_end()))
+    } else {
+        Some(text)
+    }
+}
+
+/// Whether only indentation precedes `node` on its line, which holds at the start of the
+/// file too where no whitespace node comes before it.
+fn starts_line(node: &LinkedNode) -> bool {
+    match get_prev(node) {
+        None => true,
+        Some(prev) if is_line_break(&prev) => true,
+        Some(prev) => prev.kind() == typst::syntax::SyntaxKind::Space && get_prev(&prev).is_none(),
+    }
+}
+
 pub fn collect_tidy_doc(mut node: LinkedNode<'_>) -> js_types::TidyComments {
     let origin = node.clone();
-    // Walk backwards until the first Space node starting with "\r\n"
+    // Walk backwards to the start of the line
     while let Some(prev) = get_prev(&node) {
-        if prev.kind() == typst::syntax::SyntaxKind::Space && prev.text().starts_with("\r\n") {
+        if is_line_break(&prev) {
             break;
         }
         node = prev;
     }
 
     let mut lines = Vec::new();
-    // walk back to the first non Linecomment line after '\r\n'
+    // walk back to the first non Linecomment line
     while let Some(prev) = get_prev(&node) {
-        if prev.kind() == typst::syntax::SyntaxKind::Space && prev.text().starts_with("\r\n") {
+        if is_line_break(&prev) {
             node = prev;
             if let Some(prev_comment) = get_prev(&node) {
-                let prev_kind = get_prev_kind(&prev_comment);
+                let starts_line = starts_line(&prev_comment);
                 if prev_comment.kind() == typst::syntax::SyntaxKind::LineComment
-                    && (prev_kind == Some(typst::syntax::SyntaxKind::Space)
-                        || prev_kind == Some(typst::syntax::SyntaxKind::Parbreak)
-                        || prev_kind.is_none())
+                    && starts_line
                     && prev_comment.text().starts_with("///")
                 {
                     lines.push(prev_comment.text().trim().to_string());
                     node = prev_comment;
                     continue;
                 }
+                // A single `/** */` block ends the doc comment, it may precede `///` lines
+                if prev_comment.kind() == typst::syntax::SyntaxKind::BlockComment
+                    && starts_line
+                    && prev_comment.text().starts_with("/**")
+                {
+                    lines.push(prev_comment.text().trim().to_string());
+                    node = prev_comment;
+                }
             }
         }
         break;
@@ -120,7 +344,7 @@ pub fn collect_tidy_doc(mut node: LinkedNode<'_>) -> js_types::TidyComments {
                     let name = arg
                         .children()
                         .find(|n| n.kind() == typst::syntax::SyntaxKind::Ident);
-                    default = Some(arg.children().last().unwrap().text().trim().to_string());
+                    default = default_text(&arg);
                     if let Some(name) = name {
                         name.text().trim().to_string()
                     } else {
@@ -135,15 +359,11 @@ pub fn collect_tidy_doc(mut node: LinkedNode<'_>) -> js_types::TidyComments {
             let mut node = arg;
 
             while let Some(prev) = get_prev(&node) {
-                if prev.kind() == typst::syntax::SyntaxKind::Space
-                    && prev.text().starts_with("\r\n")
-                {
+                if is_line_break(&prev) {
                     node = prev;
                     if let Some(prev_comment) = get_prev(&node) {
-                        let prev_kind = get_prev_kind(&prev_comment);
                         if prev_comment.kind() == typst::syntax::SyntaxKind::LineComment
-                            && (prev_kind == Some(typst::syntax::SyntaxKind::Space)
-                                || prev_kind.is_none())
+                            && starts_line(&prev_comment)
                             && prev_comment.text().starts_with("///")
                         {
                             lines.push(prev_comment.text().trim().to_string());
@@ -162,3 +382,85 @@ pub fn collect_tidy_doc(mut node: LinkedNode<'_>) -> js_types::TidyComments {
 
     tidy
 }
+
+#[cfg(test)]
+mod tests {
+    use typst::syntax::{Side, Source};
+
+    use super::*;
+
+    const SOURCE: &str = "#let unrelated = 1\n\n/// Adds two numbers.\n/// Negative ones too.\n#let add(\n  /// The first\n  a,\n  /// The second\n  b: 1,\n) = a + b\n";
+
+    /// The doc comments collected for the definition named `name`.
+    fn docs(text: &str, name: &str) -> js_types::TidyComments {
+        let source = Source::detached(text);
+        let offset = text.find(&format!("let {}", name)).unwrap() + 4;
+        let root = LinkedNode::new(source.root());
+        let leaf = root.leaf_at(offset + 1, Side::Before).unwrap();
+        collect_tidy_doc(leaf.parent().unwrap().clone())
+    }
+
+    #[test]
+    fn line_breaks() {
+        let source = Source::detached("a\r\nb c\n\nd");
+        let breaks = LinkedNode::new(source.root())
+            .children()
+            .filter(is_line_break)
+            .map(|node| node.text().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(breaks, ["\r\n", "\n\n"]);
+    }
+
+    #[test]
+    fn crlf_doc_comments() {
+        let lf = docs(SOURCE, "add");
+        let crlf = docs(&SOURCE.replace('\n', "\r\n"), "add");
+        assert_eq!(lf.pre, "/// Adds two numbers.\n/// Negative ones too.");
+        assert_eq!(
+            lf.args,
+            [
+                ("a".to_string(), "/// The first".to_string(), None),
+                (
+                    "b".to_string(),
+                    "/// The second".to_string(),
+                    Some("1".to_string())
+                ),
+            ]
+        );
+        assert_eq!(crlf.pre, lf.pre);
+        assert_eq!(crlf.args, lf.args);
+    }
+
+    #[test]
+    fn comments_starting_lines() {
+        for newline in ["\n", "\r\n"] {
+            let docs = |text: &str| docs(&text.replace('\n', newline), "f").pre;
+            // At the very start of the file, with and without indentation
+            assert_eq!(
+                docs("/// Starts the file\n#let f() = 1"),
+                "/// Starts the file"
+            );
+            assert_eq!(docs("  /// Indented\n#let f() = 1"), "/// Indented");
+            assert_eq!(
+                docs("/** Block */\n/// Line\n#let f() = 1"),
+                "/** Block */\n/// Line"
+            );
+            // Trailing comments of the previous line are not docs
+            assert_eq!(docs("#let x = 1 /// Trailing\n#let f() = 1"), "");
+        }
+    }
+
+    #[test]
+    fn defaults_keep_string_whitespace() {
+        let args = docs(
+            "#let f(a: \"two  spaces\", b: (1,\n    2), c: [x  \"y\"]) = a",
+            "f",
+        )
+        .args;
+        let defaults = args
+            .into_iter()
+            .map(|(_, _, default)| default.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(defaults, ["\"two  spaces\"", "(1, 2)", "[x \"y\"]"]);
+    }
+}
@@ -0,0 +1,15 @@
+use wasm_bindgen::JsValue;
+
+/// Defines the global functions the host provides to the compiler (`logWasm`, `errorWasm`
+/// and `emitEvent`) as no-ops.
+pub fn host_functions() {
+    let global = js_sys::global();
+    for name in ["logWasm", "errorWasm", "emitEvent"] {
+        js_sys::Reflect::set(
+            &global,
+            &JsValue::from_str(name),
+            &js_sys::Function::new_no_args(""),
+        )
+        .unwrap();
+    }
+}
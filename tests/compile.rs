@@ -0,0 +1,77 @@
+//! Compiles in Node.js: `wasm-pack test --node`.
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use typst_flow_wasm::SuiteCore;
+use wasm_bindgen_test::*;
+
+fn page_sizes(core: &SuiteCore) -> Vec<(f64, f64)> {
+    core.page_sizes()
+        .unwrap()
+        .iter()
+        .map(|size| (size.width_pt, size.height_pt))
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn compile_file_keeps_main_document() {
+    common::host_functions();
+    let mut core = SuiteCore::new("main.typ".into());
+    core.add_file(
+        "main.typ".into(),
+        "#set page(width: 100pt, height: 200pt)\nMain".into(),
+    )
+    .unwrap();
+    core.add_file(
+        "chapter.typ".into(),
+        "#set page(width: 300pt, height: 400pt)\nChapter".into(),
+    )
+    .unwrap();
+
+    assert!(core.compile(true).is_ok());
+    assert_eq!(page_sizes(&core), [(100.0, 200.0)]);
+    assert!(core.compile_file("chapter.typ".into(), true).is_ok());
+    assert_eq!(page_sizes(&core), [(100.0, 200.0)]);
+}
+
+/// Stages reported to the progress callback since the last call.
+fn take_stages() -> Vec<String> {
+    let stages = js_sys::Reflect::get(&js_sys::global(), &"stages".into()).unwrap();
+    js_sys::Reflect::set(&js_sys::global(), &"stages".into(), &js_sys::Array::new()).unwrap();
+    if !js_sys::Array::is_array(&stages) {
+        return Vec::new();
+    }
+    js_sys::Array::from(&stages)
+        .iter()
+        .filter_map(|stage| stage.as_string())
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn progress_only_while_compiling() {
+    common::host_functions();
+    take_stages();
+    let mut core = SuiteCore::new("main.typ".into());
+    core.add_file("main.typ".into(), "= Title\nText".into())
+        .unwrap();
+    core.set_progress_callback(Some(js_sys::Function::new_with_args(
+        "stage",
+        "globalThis.stages.push(stage)",
+    )));
+
+    core.autocomplete("main.typ".into(), 3, None, None, None)
+        .unwrap();
+    assert!(take_stages().is_empty());
+
+    assert!(core.diagnostics().is_empty());
+    assert_eq!(take_stages(), ["start", "parse", "done"]);
+
+    assert!(core.compile(true).is_ok());
+    assert_eq!(
+        take_stages(),
+        ["start", "parse", "layout", "render", "done"]
+    );
+    assert!(core.diagnostics().is_empty());
+    assert_eq!(take_stages(), ["start", "parse", "done"]);
+}
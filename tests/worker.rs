@@ -0,0 +1,34 @@
+//! Runs in a dedicated web worker, where `web_sys::window()` is `None` and packages are
+//! fetched through the `WorkerGlobalScope`: `wasm-pack test --headless --chrome`.
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use typst_flow_wasm::SuiteCore;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+#[wasm_bindgen_test]
+async fn compile_with_package_in_worker() {
+    assert!(web_sys::window().is_none());
+    common::host_functions();
+
+    let mut core = SuiteCore::new("main.typ".into());
+    core.set_auto_register_packages(true);
+    core.add_file(
+        "main.typ".into(),
+        "#import \"@preview/oxifmt:0.2.1\": strfmt\n#strfmt(\"{} + {}\", 1, 2)".into(),
+    )
+    .unwrap();
+
+    // The first pass registers the import, which is then downloaded
+    assert!(!core.diagnostics().is_empty());
+    JsFuture::from(core.prefetch_packages()).await.unwrap();
+
+    let pages = core
+        .compile(true)
+        .unwrap_or_else(|errors| panic!("compile failed with {} diagnostics", errors.len()));
+    assert_eq!(pages.len(), 1);
+}